@@ -1,8 +1,30 @@
-use crate::daemon::structs::{Bri, DaemonCmdClient, DaemonCmdType, MonitorClient, Vol};
-use clap::{Parser, Subcommand};
+use crate::daemon::renderer::config::{check_config, default_config_path, AppConf};
+use crate::daemon::renderer::config_init::init_config;
+use crate::daemon::renderer::css::css_classes;
+use crate::daemon::structs::{
+    Bri, DaemonCmdClient, DaemonCmdType, MonitorClient, Notif, Vol, VolDevice,
+};
+use crate::utils::{EXIT_FAILED, EXIT_USAGE};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use super::output;
 
 #[derive(Parser)]
 pub struct Args {
+    #[clap(
+        long = "socket",
+        env = "DVVIDGET_SOCKET",
+        global = true,
+        help = "Specify the socket path of the daemon to talk to"
+    )]
+    pub socket: Option<String>,
+    #[clap(
+        long = "json",
+        global = true,
+        help = "Print responses and events as JSON objects, in the same format as the JSON protocol"
+    )]
+    pub json: bool,
     #[clap(subcommand)]
     pub commands: Command,
 }
@@ -31,9 +53,33 @@ pub enum Command {
         #[clap(
             short,
             long = "monitor",
-            help = "Specify which monitor to display the volume panel on (defaults to all monitors if not specified)"
+            value_parser = parse_monitor,
+            help = "Specify which monitor to display the volume panel on (an index, a connector name like DP-1, a model, \"focused\" or \"all\", defaults to show_on in the config if not specified)"
+        )]
+        monitor: Option<MonitorClient>,
+        #[clap(
+            long = "mic",
+            help = "Control the default source with the microphone panel instead",
+            conflicts_with_all = ["sink", "source", "stream"]
+        )]
+        mic: bool,
+        #[clap(
+            long = "sink",
+            help = "Control the sink with the given name, without a panel",
+            conflicts_with_all = ["source", "stream"]
         )]
-        monitor: Option<usize>,
+        sink: Option<String>,
+        #[clap(
+            long = "source",
+            help = "Control the source with the given name, without a panel",
+            conflicts_with = "stream"
+        )]
+        source: Option<String>,
+        #[clap(
+            long = "stream",
+            help = "Control the playing stream with the given index or application name, without a panel"
+        )]
+        stream: Option<String>,
         #[clap(subcommand)]
         actions: VolCmd,
     },
@@ -42,9 +88,10 @@ pub enum Command {
         #[clap(
             short,
             long = "monitor",
-            help = "Specify which monitor to display the brightness panel on (defaults to all monitors if not specified)"
+            value_parser = parse_monitor,
+            help = "Specify which monitor to display the brightness panel on (an index, a connector name like DP-1, a model, \"focused\" or \"all\", defaults to show_on in the config if not specified). With the ddc provider only that monitor's brightness changes"
         )]
-        monitor: Option<usize>,
+        monitor: Option<MonitorClient>,
         #[clap(subcommand)]
         actions: BriCmd,
     },
@@ -53,12 +100,44 @@ pub enum Command {
         #[clap(
             short,
             long = "monitor",
-            help = "Specify which monitor to display dvoty on (defaults to all monitors if not specified)"
+            value_parser = parse_monitor,
+            help = "Specify which monitor to display dvoty on (an index, a connector name like DP-1, a model, \"focused\" or \"all\", defaults to show_on in the config if not specified)"
         )]
-        monitor: Option<usize>,
+        monitor: Option<MonitorClient>,
         #[clap(subcommand)]
         actions: DvotyCmd,
     },
+    #[clap(about = "Configure the notification center")]
+    Notification {
+        #[clap(
+            short,
+            long = "monitor",
+            value_parser = parse_monitor,
+            help = "Specify which monitor to display the notification center on (an index, a connector name like DP-1, a model, \"focused\" or \"all\", defaults to show_on in the config if not specified)"
+        )]
+        monitor: Option<MonitorClient>,
+        #[clap(subcommand)]
+        actions: NotifCmd,
+    },
+    #[clap(
+        about = "Print volume, brightness, do not disturb and widget visibility changes as they happen"
+    )]
+    Subscribe {
+        #[clap(
+            short,
+            long = "monitor",
+            value_parser = parse_monitor,
+            help = "Only print the changes for this monitor (an index, a connector name like DP-1, a model or \"focused\", defaults to all monitors if not specified)"
+        )]
+        monitor: Option<MonitorClient>,
+    },
+    #[clap(about = "List the monitors the daemon knows about, with the index and names -m takes")]
+    Monitors,
+    #[clap(about = "Check the config or print it with the defaults filled in")]
+    Config {
+        #[clap(subcommand)]
+        actions: ConfigCmd,
+    },
 }
 #[derive(Subcommand)]
 pub enum DaemonSubCmd {
@@ -66,6 +145,39 @@ pub enum DaemonSubCmd {
     Start,
     #[clap(about = "Shutdown the daemon")]
     Shutdown,
+    #[clap(about = "Print the version and capabilities of the running daemon")]
+    Info,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCmd {
+    #[clap(about = "Report every mistake in the config, exits with an error if there is any")]
+    Check {
+        #[clap(help = "The config to check, defaults to the one the daemon loads")]
+        path: Option<String>,
+    },
+    #[clap(about = "Print the config the daemon would run with as TOML, defaults filled in")]
+    Dump {
+        #[clap(help = "The config to print, defaults to the one the daemon loads")]
+        path: Option<String>,
+    },
+    #[clap(
+        about = "Write a commented config with every default and a starter stylesheet next to it"
+    )]
+    Init {
+        #[clap(help = "Where to write the config, defaults to the one the daemon loads")]
+        path: Option<String>,
+        #[clap(
+            long = "force",
+            help = "Overwrite the config and stylesheet if they exist"
+        )]
+        force: bool,
+        #[clap(
+            long = "print-css-classes",
+            help = "Only print the css classes the widgets use, by widget"
+        )]
+        print_css_classes: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -110,6 +222,8 @@ pub enum VolCmd {
         about = "Show the scale. If there is a number given, close the scale after the given number seconds"
     )]
     Open { time: Option<f64> },
+    #[clap(about = "List the sinks, sources and playing streams")]
+    List,
 }
 
 #[derive(Subcommand)]
@@ -122,64 +236,172 @@ pub enum DvotyCmd {
     Toggle,
 }
 
+#[derive(Subcommand)]
+pub enum NotifCmd {
+    #[clap(about = "Open the notification center")]
+    Open,
+    #[clap(about = "Close the notification center")]
+    Close,
+    #[clap(about = "Toggle the notification center")]
+    Toggle,
+    #[clap(about = "Remove one notification from the history")]
+    Clear { id: u64 },
+    #[clap(about = "Remove every notification of an app from the history")]
+    ClearApp { name: String },
+    #[clap(about = "Remove every notification from the history")]
+    ClearAll,
+    #[clap(
+        about = "Turn do not disturb on, off or toggle it, prints whether it's on without an argument"
+    )]
+    Dnd { state: Option<DndState> },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DndState {
+    On,
+    Off,
+    Toggle,
+}
+
+// numbers are indices, anything else is left for the daemon to look up
+fn parse_monitor(val: &str) -> Result<MonitorClient, String> {
+    if let Ok(ind) = val.parse::<usize>() {
+        Ok(MonitorClient::One(ind))
+    } else if val.eq_ignore_ascii_case("all") {
+        Ok(MonitorClient::All)
+    } else if val.eq_ignore_ascii_case("focused") {
+        Ok(MonitorClient::Focused)
+    } else if val.is_empty() {
+        Err("The monitor name is empty".into())
+    } else {
+        Ok(MonitorClient::Name(val.to_string()))
+    }
+}
+
 fn daemon_args(
     config_path: Option<String>,
     socket_path: Option<String>,
     option: Option<DaemonSubCmd>,
+    json: bool,
 ) {
-    if option.is_none() {
-        if let Err(e) = crate::daemon::start_daemon(config_path, socket_path) {
-            println!("Error starting the daemon: {:?}", e)
-        };
-    } else {
-        match option.unwrap() {
-            DaemonSubCmd::Start => {
-                if let Err(e) = crate::daemon::start_daemon(config_path, socket_path) {
-                    println!("Error starting the daemon: {:?}", e)
-                };
+    match option.unwrap_or(DaemonSubCmd::Start) {
+        DaemonSubCmd::Start => {
+            if let Err(e) = crate::daemon::start_daemon(config_path, socket_path) {
+                eprintln!("Error starting the daemon: {}", e);
+                std::process::exit(EXIT_FAILED);
+            };
+        }
+        DaemonSubCmd::Shutdown => send(
+            DaemonCmdClient {
+                monitor: MonitorClient::All,
+                cmd: DaemonCmdType::ShutDown,
+            },
+            json,
+        ),
+        DaemonSubCmd::Info => match crate::cli::daemon_info() {
+            Ok(handshake) => output::print_info(&handshake, json),
+            Err(e) => output::exit_with(e),
+        },
+    }
+}
+
+// works on the file directly, no daemon needed
+fn config_args(actions: ConfigCmd, json: bool) {
+    match actions {
+        ConfigCmd::Check { path } => {
+            let path = path.map(PathBuf::from).unwrap_or_else(default_config_path);
+            match check_config(&path) {
+                Ok((_, issues)) => {
+                    output::print_config_issues(&path, &issues, json);
+                    if !issues.is_empty() {
+                        std::process::exit(EXIT_FAILED);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(EXIT_FAILED);
+                }
             }
-            DaemonSubCmd::Shutdown => {
-                if let Err(e) = crate::cli::send_evt(crate::daemon::structs::DaemonCmdClient {
-                    monitor: MonitorClient::All,
-                    cmd: DaemonCmdType::ShutDown,
-                }) {
-                    println!("Error sending event: {:?}", e)
+        }
+        ConfigCmd::Dump { path } => {
+            let path = path.map(PathBuf::from).unwrap_or_else(default_config_path);
+            // the issues go to stderr so the output stays a valid config
+            let config = match check_config(&path) {
+                Ok((config, issues)) => {
+                    for issue in issues {
+                        eprintln!("{}", issue);
+                    }
+                    config
+                }
+                Err(e) => {
+                    eprintln!("{}\nGo with default", e);
+                    AppConf::default()
+                }
+            };
+            output::print_config(&config, json);
+        }
+        ConfigCmd::Init {
+            print_css_classes: true,
+            ..
+        } => output::print_css_classes(&css_classes(), json),
+        ConfigCmd::Init { path, force, .. } => {
+            let path = path.map(PathBuf::from).unwrap_or_else(default_config_path);
+            match init_config(&path, force) {
+                Ok(css_path) => {
+                    println!("Wrote {} and {}", path.display(), css_path.display());
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(EXIT_FAILED);
                 }
             }
         }
     }
 }
 
-fn volume_args(actions: VolCmd, monitor: Option<usize>) {
-    let evt = match actions {
+fn volume_args(
+    actions: VolCmd,
+    monitor: Option<MonitorClient>,
+    mic: bool,
+    device: Option<VolDevice>,
+) -> DaemonCmdClient {
+    let vol = match actions {
         VolCmd::SetMute { value } => match value {
-            Some(val) => DaemonCmdType::Vol(Vol::SetMute(val)),
-            None => DaemonCmdType::Vol(Vol::ToggleMute),
+            Some(val) => Vol::SetMute(val),
+            None => Vol::ToggleMute,
         },
-        VolCmd::GetMute => DaemonCmdType::Vol(Vol::GetMute),
-        VolCmd::Get => DaemonCmdType::Vol(Vol::Get),
-        VolCmd::SetRough { value } => DaemonCmdType::Vol(Vol::SetRough(value as f64)),
-        VolCmd::Set { value } => DaemonCmdType::Vol(Vol::Set(value as f64)),
-        VolCmd::Inc { value } => DaemonCmdType::Vol(Vol::Inc(value as f64)),
-        VolCmd::Dec { value } => DaemonCmdType::Vol(Vol::Dec(value as f64)),
-        VolCmd::Close => DaemonCmdType::Vol(Vol::Close),
+        VolCmd::GetMute => Vol::GetMute,
+        VolCmd::Get => Vol::Get,
+        VolCmd::SetRough { value } => Vol::SetRough(value as f64),
+        VolCmd::Set { value } => Vol::Set(value as f64),
+        VolCmd::Inc { value } => Vol::Inc(value as f64),
+        VolCmd::Dec { value } => Vol::Dec(value as f64),
+        VolCmd::Close => Vol::Close,
         VolCmd::Open { time } => {
             if let Some(t) = time {
-                DaemonCmdType::Vol(Vol::OpenTimed(t))
+                Vol::OpenTimed(t)
             } else {
-                DaemonCmdType::Vol(Vol::Open)
+                Vol::Open
             }
         }
+        VolCmd::List => Vol::List,
+    };
+
+    let evt = if let Some(device) = device {
+        DaemonCmdType::VolDevice(device, vol)
+    } else if mic {
+        DaemonCmdType::Mic(vol)
+    } else {
+        DaemonCmdType::Vol(vol)
     };
-    if let Err(e) = crate::cli::send_evt(DaemonCmdClient {
-        monitor: monitor.map_or_else(|| MonitorClient::All, MonitorClient::One),
+
+    DaemonCmdClient {
+        monitor: monitor.unwrap_or(MonitorClient::Default),
         cmd: evt,
-    }) {
-        println!("Err Sending event: {:?}", e);
     }
 }
 
-fn bri_args(actions: BriCmd, monitor: Option<usize>) {
+fn bri_args(actions: BriCmd, monitor: Option<MonitorClient>) -> DaemonCmdClient {
     let evt = match actions {
         BriCmd::Get => DaemonCmdType::Bri(Bri::Get),
         BriCmd::SetRough { value } => DaemonCmdType::Bri(Bri::SetRough(value as f64)),
@@ -195,27 +417,154 @@ fn bri_args(actions: BriCmd, monitor: Option<usize>) {
             }
         }
     };
-    if let Err(e) = crate::cli::send_evt(DaemonCmdClient {
-        monitor: monitor.map_or_else(|| MonitorClient::All, MonitorClient::One),
+
+    DaemonCmdClient {
+        monitor: monitor.unwrap_or(MonitorClient::Default),
         cmd: evt,
-    }) {
-        println!("Err Sending event: {:?}", e);
     }
 }
 
-fn dvoty_args(actions: DvotyCmd, monitor: Option<usize>) {
-    let command = {
-        let cmd = match actions {
-            DvotyCmd::Open => DaemonCmdType::Dvoty(crate::daemon::structs::Dvoty::Open),
-            DvotyCmd::Close => DaemonCmdType::Dvoty(crate::daemon::structs::Dvoty::Close),
-            DvotyCmd::Toggle => DaemonCmdType::Dvoty(crate::daemon::structs::Dvoty::Toggle),
+fn dvoty_args(actions: DvotyCmd, monitor: Option<MonitorClient>) -> DaemonCmdClient {
+    let cmd = match actions {
+        DvotyCmd::Open => DaemonCmdType::Dvoty(crate::daemon::structs::Dvoty::Open),
+        DvotyCmd::Close => DaemonCmdType::Dvoty(crate::daemon::structs::Dvoty::Close),
+        DvotyCmd::Toggle => DaemonCmdType::Dvoty(crate::daemon::structs::Dvoty::Toggle),
+    };
+
+    DaemonCmdClient {
+        monitor: monitor.unwrap_or(MonitorClient::Default),
+        cmd,
+    }
+}
+
+fn notif_args(actions: NotifCmd, monitor: Option<MonitorClient>) -> DaemonCmdClient {
+    let cmd = match actions {
+        NotifCmd::Open => DaemonCmdType::Notif(Notif::CenterOpen),
+        NotifCmd::Close => DaemonCmdType::Notif(Notif::CenterClose),
+        NotifCmd::Toggle => DaemonCmdType::Notif(Notif::CenterToggle),
+        NotifCmd::Clear { id } => DaemonCmdType::Notif(Notif::Clear(id)),
+        NotifCmd::ClearApp { name } => DaemonCmdType::Notif(Notif::ClearApp(name)),
+        NotifCmd::ClearAll => DaemonCmdType::Notif(Notif::ClearAll),
+        NotifCmd::Dnd { state } => match state {
+            Some(DndState::On) => DaemonCmdType::Notif(Notif::SetDnd(true)),
+            Some(DndState::Off) => DaemonCmdType::Notif(Notif::SetDnd(false)),
+            Some(DndState::Toggle) => DaemonCmdType::Notif(Notif::ToggleDnd),
+            None => DaemonCmdType::Notif(Notif::GetDnd),
+        },
+    };
+
+    DaemonCmdClient {
+        monitor: monitor.unwrap_or(MonitorClient::Default),
+        cmd,
+    }
+}
+
+// the commands that only talk to the daemon, None for the rest
+fn client_cmd(command: Command) -> Option<DaemonCmdClient> {
+    match command {
+        Command::Volume {
+            monitor,
+            mic,
+            sink,
+            source,
+            stream,
+            actions,
+        } => {
+            let device = sink
+                .map(VolDevice::Sink)
+                .or(source.map(VolDevice::Source))
+                .or(stream.map(VolDevice::Stream));
+            Some(volume_args(actions, monitor, mic, device))
+        }
+
+        Command::Brightness { monitor, actions } => Some(bri_args(actions, monitor)),
+
+        Command::Dvoty { monitor, actions } => Some(dvoty_args(actions, monitor)),
+
+        Command::Notification { monitor, actions } => Some(notif_args(actions, monitor)),
+
+        Command::Monitors => Some(DaemonCmdClient {
+            monitor: MonitorClient::All,
+            cmd: DaemonCmdType::ListMonitors,
+        }),
+
+        Command::Daemon { .. } | Command::Subscribe { .. } | Command::Config { .. } => None,
+    }
+}
+
+fn send(cmd: DaemonCmdClient, json: bool) {
+    output::finish(crate::cli::send_evt(cmd), json);
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    std::process::exit(EXIT_USAGE);
+}
+
+/// splits the arguments at every `+`, each part keeps the program name so it parses on its own.
+/// nothing after `--` is split, it's left to clap as the end of the options
+pub fn split_batch(args: Vec<String>) -> Vec<Vec<String>> {
+    let mut iter = args.into_iter();
+    let program = iter.next().unwrap_or_default();
+    let mut result = vec![vec![program.clone()]];
+    let mut options = true;
+
+    for arg in iter {
+        if options && arg == "+" {
+            result.push(vec![program.clone()]);
+            continue;
+        }
+
+        options &= arg != "--";
+        result.last_mut().unwrap().push(arg);
+    }
+
+    result
+}
+
+// `dvvidget volume set 50 + volume set-mute false` sends both commands in one request
+pub fn handle_batch(batch: Vec<Args>) {
+    let mut monitor = None;
+    let mut cmds = vec![];
+    let mut json = false;
+
+    for args in batch {
+        set_socket_path(args.socket);
+        json |= args.json;
+
+        let cmd = if let Some(c) = client_cmd(args.commands) {
+            c
+        } else {
+            usage_error(
+                "Only volume, brightness, dvoty, notification and monitors commands can be batched",
+            );
         };
-        DaemonCmdClient {
-            monitor: monitor.map_or_else(|| MonitorClient::All, MonitorClient::One),
-            cmd,
+
+        match &monitor {
+            None => monitor = Some(cmd.monitor),
+            Some(m) if *m != cmd.monitor => {
+                usage_error("Every command of a batch has to target the same monitor");
+            }
+            _ => {}
         }
-    };
-    crate::cli::send_evt(command).unwrap_or_else(|e| println!("Error seding event: {:?}", e));
+
+        cmds.push(cmd.cmd);
+    }
+
+    send(
+        DaemonCmdClient {
+            monitor: monitor.unwrap_or(MonitorClient::Default),
+            cmd: DaemonCmdType::Batch(cmds),
+        },
+        json,
+    );
+}
+
+fn set_socket_path(path: Option<String>) {
+    if let Some(p) = path {
+        // only the first part of a batch gets to set it
+        let _ = crate::cli::SOCKET_PATH.set(p);
+    }
 }
 
 pub fn handle_args(args: Args) {
@@ -225,19 +574,75 @@ pub fn handle_args(args: Args) {
             config_path,
             option,
         } => {
-            daemon_args(config_path, socket_path, option);
+            let socket_path = socket_path.or(args.socket);
+            set_socket_path(socket_path.clone());
+            daemon_args(config_path, socket_path, option, args.json);
         }
 
-        Command::Volume { monitor, actions } => {
-            volume_args(actions, monitor);
-        }
+        Command::Config { actions } => config_args(actions, args.json),
 
-        Command::Brightness { monitor, actions } => {
-            bri_args(actions, monitor);
+        Command::Subscribe { monitor } => {
+            set_socket_path(args.socket);
+            if let Err(e) = crate::cli::subscribe(monitor.unwrap_or(MonitorClient::All), |evt| {
+                output::print_event(&evt, args.json)
+            }) {
+                output::exit_with(e);
+            }
         }
 
-        Command::Dvoty { monitor, actions } => {
-            dvoty_args(actions, monitor);
+        command => {
+            set_socket_path(args.socket);
+            if let Some(cmd) = client_cmd(command) {
+                send(cmd, args.json);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::split_batch;
+
+    fn split(args: &str) -> Vec<String> {
+        split_batch(args.split(' ').map(String::from).collect())
+            .into_iter()
+            .map(|part| part.join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn single_command() {
+        assert_eq!(split("dvvidget volume set 50"), ["dvvidget volume set 50"]);
+    }
+
+    #[test]
+    fn splits_at_plus() {
+        assert_eq!(
+            split("dvvidget volume set 50 + brightness -m 1 inc 5 + volume open"),
+            [
+                "dvvidget volume set 50",
+                "dvvidget brightness -m 1 inc 5",
+                "dvvidget volume open"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_end_of_options() {
+        assert_eq!(
+            split("dvvidget notification clear-app -- -weird-app"),
+            ["dvvidget notification clear-app -- -weird-app"]
+        );
+    }
+
+    #[test]
+    fn no_split_after_end_of_options() {
+        assert_eq!(
+            split("dvvidget volume open + notification clear-app -- +"),
+            [
+                "dvvidget volume open",
+                "dvvidget notification clear-app -- +"
+            ]
+        );
+    }
+}
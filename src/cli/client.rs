@@ -1,17 +1,57 @@
 use std::path::Path;
+use std::time::Duration;
 
 use crate::{
     daemon::{
-        server::default_socket_path,
-        structs::{DaemonCmdClient, DaemonCmdType, DaemonRes},
+        protocol::{self, Handshake, PROTOCOL_VERSION},
+        structs::{DaemonCmdClient, DaemonCmdType, DaemonEvent, DaemonRes, MonitorClient},
     },
     utils::ClientErr,
 };
 use anyhow::Context;
-use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::UnixStream,
-};
+use tokio::net::UnixStream;
+
+// daemons from before the handshake wait for more bytes instead of answering
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// connects and checks that the daemon speaks the same protocol
+async fn connect() -> Result<(UnixStream, Handshake), ClientErr> {
+    let mut stream = if let Ok(res) = UnixStream::connect(Path::new(&super::socket_path())).await {
+        res
+    } else {
+        return Err(ClientErr::CannotConnectServer);
+    };
+
+    if let Err(e) = protocol::write_header(&mut stream).await {
+        return Err(ClientErr::WriteErr(e.to_string()));
+    }
+
+    let version =
+        match tokio::time::timeout(HANDSHAKE_TIMEOUT, protocol::read_header(&mut stream)).await {
+            Ok(Ok(Some(v))) => v,
+            Ok(Ok(None)) => return Err(ClientErr::NotADaemon),
+            Ok(Err(e)) => return Err(ClientErr::ReadingFailed(e.to_string())),
+            Err(_) => return Err(ClientErr::NoHandshake),
+        };
+
+    let handshake: Handshake = match protocol::read_frame(&mut stream).await {
+        Ok(buf) => match bincode::deserialize(&buf) {
+            Ok(val) => val,
+            Err(e) => return Err(ClientErr::DeserializeError(e.to_string())),
+        },
+        Err(e) => return Err(ClientErr::ReadingFailed(e.to_string())),
+    };
+
+    if version != PROTOCOL_VERSION {
+        return Err(ClientErr::VersionMismatch {
+            client: PROTOCOL_VERSION,
+            daemon: version,
+            daemon_version: handshake.daemon_version,
+        });
+    }
+
+    Ok((stream, handshake))
+}
 
 async fn send_to_stream(
     evt: DaemonCmdClient,
@@ -19,48 +59,21 @@ async fn send_to_stream(
 ) -> Result<UnixStream, ClientErr> {
     let evt_buf = match bincode::serialize(&evt).context("Failed to serialize command") {
         Ok(res) => res,
-        Err(e) => return Err(ClientErr::SerializeError(evt, e.to_string())),
+        Err(e) => return Err(ClientErr::SerializeError(Box::new(evt), e.to_string())),
     };
 
-    match stream.write(&(evt_buf.len() as u32).to_le_bytes()).await {
-        Ok(_) => {}
-        Err(e) => {
-            return Err(ClientErr::WriteErr(e.to_string()));
-        }
-    }
-
-    if let Err(e) = stream.write_all(&evt_buf).await {
+    if let Err(e) = protocol::write_frame(&mut stream, &evt_buf).await {
         return Err(ClientErr::WriteErr(e.to_string()));
     }
 
     Ok(stream)
 }
 
-// the sender will send the struct in the following fashion:
-/**
- * +----------------------------+-------------+
- * | u32 size in littlen endian | actual data |
- * +----------------------------+-------------+
- */
-async fn read_res(mut stream: UnixStream) -> Result<DaemonRes, ClientErr> {
-    let mut msg_len_buf = [0u8; 4];
-    if let Err(e) = stream.read_exact(&mut msg_len_buf).await {
-        return Err(ClientErr::ReadingFailed(e.to_string()));
+async fn read_res(stream: &mut UnixStream) -> Result<DaemonRes, ClientErr> {
+    let msg_buf = match protocol::read_frame(stream).await {
+        Ok(buf) => buf,
+        Err(e) => return Err(ClientErr::ReadingFailed(e.to_string())),
     };
-    let msg_len: u32 = u32::from_le_bytes(msg_len_buf);
-
-    let mut msg_buf = Vec::<u8>::with_capacity(msg_len as usize);
-
-    // ensure we read all the info
-    while msg_buf.len() < msg_len as usize {
-        if let Err(e) = stream
-            .read_buf(&mut msg_buf)
-            .await
-            .context("Failed to read the command from the client")
-        {
-            return Err(ClientErr::ReadingFailed(e.to_string()));
-        };
-    }
 
     Ok(
         match bincode::deserialize(&msg_buf).context("Failed to deserialize command") {
@@ -70,30 +83,45 @@ async fn read_res(mut stream: UnixStream) -> Result<DaemonRes, ClientErr> {
     )
 }
 
-pub async fn send_evt_async(evt: DaemonCmdClient) -> Result<(), ClientErr> {
-    let stream: UnixStream =
-        if let Ok(res) = UnixStream::connect(Path::new(&default_socket_path())).await {
-            res
-        } else {
-            return Err(ClientErr::CannotConnectServer);
-        };
+pub async fn daemon_info_async() -> Result<Handshake, ClientErr> {
+    let (_, handshake) = connect().await?;
+
+    Ok(handshake)
+}
 
-    let stream = send_to_stream(evt.clone(), stream).await?;
+pub async fn send_evt_async(evt: DaemonCmdClient) -> Result<DaemonRes, ClientErr> {
+    let (stream, _) = connect().await?;
 
+    let mut stream = send_to_stream(evt.clone(), stream).await?;
+
+    // the daemon goes away instead of answering
     if let DaemonCmdType::ShutDown = evt.cmd {
-        println!("Signal sent");
-        return Ok(());
+        return Ok(DaemonRes::Success);
     }
 
-    let response = read_res(stream).await?;
+    read_res(&mut stream).await
+}
+
+// hands every event to on_event until the daemon goes away
+pub async fn subscribe_async(
+    monitor: MonitorClient,
+    mut on_event: impl FnMut(DaemonEvent),
+) -> Result<(), ClientErr> {
+    let (stream, _) = connect().await?;
 
-    match response {
-        DaemonRes::Failure(e) => println!("Failed: {}", e),
-        DaemonRes::Success => println!("Success"),
-        DaemonRes::GetVol(val) => println!("{}", val),
-        DaemonRes::GetMute(val) => println!("{}", val),
-        DaemonRes::GetBri(val) => println!("{}", val),
-    }
+    let mut stream = send_to_stream(
+        DaemonCmdClient {
+            monitor,
+            cmd: DaemonCmdType::Subscribe,
+        },
+        stream,
+    )
+    .await?;
 
-    Ok(())
+    loop {
+        match read_res(&mut stream).await? {
+            DaemonRes::Event(evt) => on_event(evt),
+            res => eprintln!("Unexpected response: {:?}", res),
+        }
+    }
 }
@@ -1,5 +1,20 @@
 pub mod args;
 pub mod client;
+pub mod output;
 pub mod send;
 
-pub use send::send_evt;
+use once_cell::sync::OnceCell;
+
+use crate::daemon::server::default_socket_path;
+
+pub use send::{daemon_info, send_evt, subscribe};
+
+// set from --socket or DVVIDGET_SOCKET before talking to the daemon
+pub static SOCKET_PATH: OnceCell<String> = OnceCell::new();
+
+pub fn socket_path() -> String {
+    SOCKET_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(default_socket_path)
+}
@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::daemon::protocol::Handshake;
+use crate::daemon::renderer::config::AppConf;
+use crate::daemon::renderer::config_check::ConfigIssue;
+use crate::daemon::structs::{DaemonEvent, DaemonRes, VolDevice};
+use crate::utils::{ClientErr, EXIT_FAILED};
+
+fn print_json(val: &impl Serialize) {
+    match serde_json::to_string(val) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("Failed to serialize the output: {}", e),
+    }
+}
+
+// the exit code of the failure, the first failing command's for a batch
+pub fn failure_code(res: &DaemonRes) -> Option<i32> {
+    match res {
+        DaemonRes::Failure(e) => Some(e.exit_code()),
+        DaemonRes::Batch(results) => results.iter().find_map(failure_code),
+        _ => None,
+    }
+}
+
+// values go to stdout on their own so $(dvvidget volume get) can be used as is
+pub fn print_res(res: &DaemonRes, json: bool) {
+    if json {
+        print_json(res);
+        return;
+    }
+
+    match res {
+        DaemonRes::Failure(e) => eprintln!("Failed: {}", e),
+        DaemonRes::Success => {}
+        DaemonRes::GetVol(val) => println!("{}", val),
+        DaemonRes::GetMute(val) => println!("{}", val),
+        DaemonRes::GetBri(val) => println!("{}", val),
+        DaemonRes::GetDnd(val) => println!("{}", val),
+        DaemonRes::Event(evt) => print_event(evt, false),
+        DaemonRes::Batch(results) => {
+            for res in results {
+                print_res(res, false);
+            }
+        }
+        DaemonRes::Monitors(monitors) => {
+            for (ind, info) in monitors.iter().enumerate() {
+                println!(
+                    "{}\t{}\t{} {}",
+                    ind,
+                    info.connector.as_deref().unwrap_or("-"),
+                    info.manufacturer.as_deref().unwrap_or_default(),
+                    info.model.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        DaemonRes::VolList(devices) => {
+            for info in devices {
+                let (kind, name) = match &info.device {
+                    VolDevice::Sink(name) => ("sink", name),
+                    VolDevice::Source(name) => ("source", name),
+                    VolDevice::Stream(name) => ("stream", name),
+                    VolDevice::DefaultSink | VolDevice::DefaultSource => continue,
+                };
+
+                println!(
+                    "{}{:<7}{}\t{}\t{:.0}%{}",
+                    if info.is_default { "* " } else { "  " },
+                    kind,
+                    name,
+                    info.description,
+                    info.volume,
+                    if info.muted { " [MUTED]" } else { "" }
+                );
+            }
+        }
+    }
+}
+
+pub fn print_event(evt: &DaemonEvent, json: bool) {
+    if json {
+        print_json(evt);
+        return;
+    }
+
+    match evt {
+        DaemonEvent::Vol { volume, muted } => {
+            println!("vol {}{}", volume, if *muted { " muted" } else { "" })
+        }
+        DaemonEvent::Mic { volume, muted } => {
+            println!("mic {}{}", volume, if *muted { " muted" } else { "" })
+        }
+        DaemonEvent::Bri {
+            monitor,
+            brightness,
+        } => println!("bri {} {}", monitor, brightness),
+        DaemonEvent::Dnd { enabled } => println!("dnd {}", enabled),
+        DaemonEvent::Visible {
+            widget,
+            monitor,
+            visible,
+        } => println!("visible {:?} {} {}", widget, monitor, visible),
+    }
+}
+
+pub fn print_info(handshake: &Handshake, json: bool) {
+    if json {
+        print_json(handshake);
+        return;
+    }
+
+    println!(
+        "dvvidget {} (protocol {})",
+        handshake.daemon_version, handshake.protocol_version
+    );
+    println!("capabilities: {}", handshake.capabilities.join(", "));
+}
+
+pub fn print_config_issues(path: &Path, issues: &[ConfigIssue], json: bool) {
+    if json {
+        print_json(&issues);
+        return;
+    }
+
+    if issues.is_empty() {
+        println!("{}: ok", path.display());
+    }
+
+    for issue in issues {
+        println!("{}", issue);
+    }
+}
+
+pub fn print_config(config: &AppConf, json: bool) {
+    if json {
+        print_json(config);
+        return;
+    }
+
+    match toml::to_string_pretty(config) {
+        Ok(s) => print!("{}", s),
+        Err(e) => {
+            eprintln!("Failed to serialize the config: {}", e);
+            std::process::exit(EXIT_FAILED);
+        }
+    }
+}
+
+pub fn print_css_classes(classes: &[(&str, Vec<String>)], json: bool) {
+    if json {
+        let classes: Vec<_> = classes
+            .iter()
+            .map(|(widget, classes)| serde_json::json!({ "widget": widget, "classes": classes }))
+            .collect();
+        print_json(&classes);
+        return;
+    }
+
+    for (ind, (widget, classes)) in classes.iter().enumerate() {
+        if ind > 0 {
+            println!();
+        }
+        println!("/* {} */", widget);
+        for class in classes {
+            println!(".{}", class);
+        }
+    }
+}
+
+// prints the response and exits with the failure's code if the daemon couldn't run the command
+pub fn finish(res: Result<DaemonRes, ClientErr>, json: bool) {
+    match res {
+        Ok(res) => {
+            print_res(&res, json);
+            if let Some(code) = failure_code(&res) {
+                std::process::exit(code);
+            }
+        }
+        Err(e) => exit_with(e),
+    }
+}
+
+pub fn exit_with(err: ClientErr) -> ! {
+    eprintln!("Error: {}", err);
+    std::process::exit(err.exit_code());
+}
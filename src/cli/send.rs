@@ -1,19 +1,34 @@
-use crate::{daemon::structs::DaemonCmdClient, utils::ClientErr};
+use crate::{
+    daemon::{
+        protocol::Handshake,
+        structs::{DaemonCmdClient, DaemonEvent, DaemonRes, MonitorClient},
+    },
+    utils::ClientErr,
+};
 
 use super::client;
 
-pub fn send_evt(evt: DaemonCmdClient) -> Result<(), ClientErr> {
+fn block_on<T>(f: impl std::future::Future<Output = Result<T, ClientErr>>) -> Result<T, ClientErr> {
     let rt = tokio::runtime::Builder::new_current_thread()
         .thread_name("dvvidget client")
         .enable_all()
         .build()
         .unwrap();
 
-    rt.block_on(async {
-        if let Err(e) = client::send_evt_async(evt).await {
-            println!("Error: {:?}", e);
-        }
-    });
+    rt.block_on(f)
+}
+
+pub fn send_evt(evt: DaemonCmdClient) -> Result<DaemonRes, ClientErr> {
+    block_on(client::send_evt_async(evt))
+}
+
+pub fn daemon_info() -> Result<Handshake, ClientErr> {
+    block_on(client::daemon_info_async())
+}
 
-    Ok(())
+pub fn subscribe(
+    monitor: MonitorClient,
+    on_event: impl FnMut(DaemonEvent),
+) -> Result<(), ClientErr> {
+    block_on(client::subscribe_async(monitor, on_event))
 }
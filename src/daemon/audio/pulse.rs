@@ -0,0 +1,348 @@
+use std::ffi::CString;
+use std::io::BufReader;
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use pulseaudio::protocol::{
+    self, ChannelVolume, Command, GetSinkInfo, GetSourceInfo, Prop, Props, SetDeviceMuteParams,
+    SetDeviceVolumeParams, SetStreamMuteParams, SetStreamVolumeParams, SinkInputInfo,
+    SubscriptionEvent, SubscriptionMask, Volume,
+};
+use thiserror::Error;
+
+use crate::daemon::structs::{VolDevice, VolDeviceInfo};
+
+// understood by both pulseaudio and pipewire-pulse
+pub const DEFAULT_SINK: &str = "@DEFAULT_SINK@";
+pub const DEFAULT_SOURCE: &str = "@DEFAULT_SOURCE@";
+
+#[derive(Debug, Error)]
+pub enum PulseErr {
+    #[error("Cannot find the sound server socket")]
+    NoSocket,
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Protocol error: {0}")]
+    Protocol(#[from] protocol::ProtocolError),
+    #[error("Invalid device name: {0}")]
+    InvalidName(#[from] std::ffi::NulError),
+    #[error("Cannot find the stream {0}")]
+    NoStream(String),
+}
+
+// the parts of sinks, sources and streams we care about
+struct DeviceState {
+    volume: ChannelVolume,
+    muted: bool,
+}
+
+pub struct PulseConnection {
+    sock: BufReader<UnixStream>,
+    seq: u32,
+    version: u16,
+}
+
+lazy_static! {
+    // kept open between calls, reconnected whenever a request fails
+    static ref CONNECTION: Mutex<Option<PulseConnection>> = Mutex::new(None);
+}
+
+impl PulseConnection {
+    pub fn connect() -> Result<Self, PulseErr> {
+        let socket_path = pulseaudio::socket_path_from_env().ok_or(PulseErr::NoSocket)?;
+        let stream = UnixStream::connect(socket_path)?;
+        // the gtk thread waits on these requests, never let a stuck server freeze it
+        stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+
+        let mut result = PulseConnection {
+            sock: BufReader::new(stream),
+            seq: 0,
+            version: protocol::MAX_VERSION,
+        };
+
+        let cookie = pulseaudio::cookie_path_from_env()
+            .and_then(|path| std::fs::read(path).ok())
+            .unwrap_or_default();
+
+        let auth: protocol::AuthReply = result.request(Command::Auth(protocol::AuthParams {
+            version: protocol::MAX_VERSION,
+            supports_shm: false,
+            supports_memfd: false,
+            cookie,
+        }))?;
+        result.version = std::cmp::min(protocol::MAX_VERSION, auth.version);
+
+        let mut props = Props::new();
+        props.set(Prop::ApplicationName, c"dvvidget");
+        let _: protocol::SetClientNameReply = result.request(Command::SetClientName(props))?;
+
+        Ok(result)
+    }
+
+    fn write(&mut self, cmd: Command) -> Result<(), PulseErr> {
+        self.seq = self.seq.wrapping_add(1);
+        protocol::write_command_message(self.sock.get_mut(), self.seq, &cmd, self.version)?;
+        Ok(())
+    }
+
+    fn request<T: protocol::CommandReply>(&mut self, cmd: Command) -> Result<T, PulseErr> {
+        self.write(cmd)?;
+        let (_, reply) = protocol::read_reply_message::<T>(&mut self.sock, self.version)?;
+        Ok(reply)
+    }
+
+    fn request_ack(&mut self, cmd: Command) -> Result<(), PulseErr> {
+        self.write(cmd)?;
+        protocol::read_ack_message(&mut self.sock)?;
+        Ok(())
+    }
+
+    // after this the connection only receives events, use another one for requests
+    pub fn subscribe(&mut self, mask: SubscriptionMask) -> Result<(), PulseErr> {
+        self.request_ack(Command::Subscribe(mask))
+    }
+
+    // Ok(None) when nothing arrived before the read timeout
+    pub fn next_event(&mut self) -> Result<Option<SubscriptionEvent>, PulseErr> {
+        loop {
+            match protocol::read_command_message(&mut self.sock, self.version) {
+                Ok((_, Command::SubscribeEvent(evt))) => return Ok(Some(evt)),
+                Ok(_) => continue,
+                Err(protocol::ProtocolError::Io(e))
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn sink_info(&mut self, name: &str) -> Result<protocol::SinkInfo, PulseErr> {
+        self.request(Command::GetSinkInfo(GetSinkInfo {
+            index: None,
+            name: Some(CString::new(name)?),
+        }))
+    }
+
+    fn source_info(&mut self, name: &str) -> Result<protocol::SourceInfo, PulseErr> {
+        self.request(Command::GetSourceInfo(GetSourceInfo {
+            index: None,
+            name: Some(CString::new(name)?),
+        }))
+    }
+
+    // by index, then by application name or binary
+    fn find_stream(&mut self, key: &str) -> Result<SinkInputInfo, PulseErr> {
+        let streams: protocol::SinkInputInfoList = self.request(Command::GetSinkInputInfoList)?;
+        let index = key.parse::<u32>().ok();
+
+        streams
+            .into_iter()
+            .find(|stream| {
+                Some(stream.index) == index
+                    || [Prop::ApplicationName, Prop::ApplicationProcessBinary]
+                        .into_iter()
+                        .filter_map(|prop| stream.props.get(prop))
+                        .any(|val| prop_str(val).eq_ignore_ascii_case(key))
+            })
+            .ok_or_else(|| PulseErr::NoStream(key.to_string()))
+    }
+
+    fn state(&mut self, device: &VolDevice) -> Result<DeviceState, PulseErr> {
+        Ok(match device {
+            VolDevice::DefaultSink | VolDevice::Sink(_) => {
+                let info = self.sink_info(device_name(device))?;
+                DeviceState {
+                    volume: info.cvolume,
+                    muted: info.muted,
+                }
+            }
+            VolDevice::DefaultSource | VolDevice::Source(_) => {
+                let info = self.source_info(device_name(device))?;
+                DeviceState {
+                    volume: info.cvolume,
+                    muted: info.muted,
+                }
+            }
+            VolDevice::Stream(key) => {
+                let info = self.find_stream(key)?;
+                DeviceState {
+                    volume: info.cvolume,
+                    muted: info.muted,
+                }
+            }
+        })
+    }
+
+    // returns the volume in percent and the mute state
+    pub fn get_volume(&mut self, device: &VolDevice) -> Result<(f64, bool), PulseErr> {
+        let state = self.state(device)?;
+        Ok((to_percent(&state.volume), state.muted))
+    }
+
+    pub fn set_volume(&mut self, device: &VolDevice, val: f64) -> Result<(), PulseErr> {
+        // same as pactl with a single value, every channel gets the same volume
+        let channels = self.state(device)?.volume.channels().len().max(1);
+        let mut volume = ChannelVolume::empty();
+        for _ in 0..channels {
+            volume.push(from_percent(val));
+        }
+
+        let cmd = match device {
+            VolDevice::DefaultSink | VolDevice::Sink(_) => {
+                Command::SetSinkVolume(SetDeviceVolumeParams {
+                    device_index: None,
+                    device_name: Some(CString::new(device_name(device))?),
+                    volume,
+                })
+            }
+            VolDevice::DefaultSource | VolDevice::Source(_) => {
+                Command::SetSourceVolume(SetDeviceVolumeParams {
+                    device_index: None,
+                    device_name: Some(CString::new(device_name(device))?),
+                    volume,
+                })
+            }
+            VolDevice::Stream(key) => Command::SetSinkInputVolume(SetStreamVolumeParams {
+                index: self.find_stream(key)?.index,
+                volume,
+            }),
+        };
+
+        self.request_ack(cmd)
+    }
+
+    pub fn set_mute(&mut self, device: &VolDevice, mute: bool) -> Result<(), PulseErr> {
+        let cmd = match device {
+            VolDevice::DefaultSink | VolDevice::Sink(_) => {
+                Command::SetSinkMute(SetDeviceMuteParams {
+                    device_index: None,
+                    device_name: Some(CString::new(device_name(device))?),
+                    mute,
+                })
+            }
+            VolDevice::DefaultSource | VolDevice::Source(_) => {
+                Command::SetSourceMute(SetDeviceMuteParams {
+                    device_index: None,
+                    device_name: Some(CString::new(device_name(device))?),
+                    mute,
+                })
+            }
+            VolDevice::Stream(key) => Command::SetSinkInputMute(SetStreamMuteParams {
+                index: self.find_stream(key)?.index,
+                mute,
+            }),
+        };
+
+        self.request_ack(cmd)
+    }
+
+    // every sink, source (monitors excluded) and playing stream
+    pub fn list(&mut self) -> Result<Vec<VolDeviceInfo>, PulseErr> {
+        let server: protocol::ServerInfo = self.request(Command::GetServerInfo)?;
+        let sinks: protocol::SinkInfoList = self.request(Command::GetSinkInfoList)?;
+        let sources: protocol::SourceInfoList = self.request(Command::GetSourceInfoList)?;
+        let streams: protocol::SinkInputInfoList = self.request(Command::GetSinkInputInfoList)?;
+
+        let mut result = vec![];
+
+        for sink in sinks {
+            result.push(VolDeviceInfo {
+                is_default: server.default_sink_name.as_ref() == Some(&sink.name),
+                description: c_str(sink.description.as_ref().unwrap_or(&sink.name)),
+                device: VolDevice::Sink(c_str(&sink.name)),
+                volume: to_percent(&sink.cvolume),
+                muted: sink.muted,
+            });
+        }
+
+        for source in sources
+            .into_iter()
+            .filter(|s| s.monitor_of_sink_index.is_none())
+        {
+            result.push(VolDeviceInfo {
+                is_default: server.default_source_name.as_ref() == Some(&source.name),
+                description: c_str(source.description.as_ref().unwrap_or(&source.name)),
+                device: VolDevice::Source(c_str(&source.name)),
+                volume: to_percent(&source.cvolume),
+                muted: source.muted,
+            });
+        }
+
+        for stream in streams.into_iter().filter(|s| s.has_volume) {
+            result.push(VolDeviceInfo {
+                is_default: false,
+                description: stream
+                    .props
+                    .get(Prop::ApplicationName)
+                    .map(prop_str)
+                    .unwrap_or_else(|| c_str(&stream.name)),
+                device: VolDevice::Stream(stream.index.to_string()),
+                volume: to_percent(&stream.cvolume),
+                muted: stream.muted,
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+fn device_name(device: &VolDevice) -> &str {
+    match device {
+        VolDevice::DefaultSink => DEFAULT_SINK,
+        VolDevice::DefaultSource => DEFAULT_SOURCE,
+        VolDevice::Sink(name) | VolDevice::Source(name) | VolDevice::Stream(name) => name,
+    }
+}
+
+fn c_str(val: &CString) -> String {
+    val.to_string_lossy().into_owned()
+}
+
+// property values are nul terminated strings
+fn prop_str(val: &[u8]) -> String {
+    String::from_utf8_lossy(val.strip_suffix(&[0]).unwrap_or(val)).into_owned()
+}
+
+fn to_percent(volume: &ChannelVolume) -> f64 {
+    let max = volume
+        .channels()
+        .iter()
+        .map(|v| v.as_u32())
+        .max()
+        .unwrap_or_default();
+
+    max as f64 / Volume::NORM.as_u32() as f64 * 100f64
+}
+
+fn from_percent(val: f64) -> Volume {
+    Volume::from_u32_clamped((val.max(0f64) / 100f64 * Volume::NORM.as_u32() as f64) as u32)
+}
+
+/// runs the given request on the shared connection, retrying once with a fresh connection
+pub fn with_connection<T>(
+    f: impl Fn(&mut PulseConnection) -> Result<T, PulseErr>,
+) -> Result<T, PulseErr> {
+    let mut guard = CONNECTION.lock().unwrap();
+
+    if let Some(conn) = guard.as_mut() {
+        match f(conn) {
+            Ok(res) => return Ok(res),
+            Err(e) => println!("Pulse: Request failed, reconnecting: {}", e),
+        }
+    }
+
+    *guard = None;
+    let mut conn = PulseConnection::connect()?;
+    let result = f(&mut conn);
+    *guard = Some(conn);
+
+    result
+}
@@ -0,0 +1,283 @@
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+const IPC_TIMEOUT: Duration = Duration::from_secs(1);
+
+// sway's GET_OUTPUTS
+const SWAY_GET_OUTPUTS: u32 = 3;
+const SWAY_MAGIC: &[u8] = b"i3-ipc";
+
+// gdk has no idea which output has focus, only the compositor does
+pub trait Compositor {
+    fn name(&self) -> &'static str;
+
+    // the connector name of the focused output
+    fn focused_output(&self) -> std::io::Result<Option<String>>;
+}
+
+// the only fields hyprland and sway agree on
+#[derive(Deserialize)]
+struct Output {
+    name: String,
+    focused: bool,
+}
+
+fn connect(socket: &PathBuf) -> std::io::Result<UnixStream> {
+    let stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(IPC_TIMEOUT))?;
+    stream.set_write_timeout(Some(IPC_TIMEOUT))?;
+    Ok(stream)
+}
+
+fn focused(outputs: Vec<Output>) -> Option<String> {
+    outputs.into_iter().find(|o| o.focused).map(|o| o.name)
+}
+
+pub struct Hyprland {
+    socket: PathBuf,
+}
+
+impl Hyprland {
+    pub fn new(socket: PathBuf) -> Self {
+        Hyprland { socket }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+
+        // hyprland moved its sockets from /tmp to the runtime dir at some point
+        std::env::var("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr"))
+            .into_iter()
+            .chain([PathBuf::from("/tmp/hypr")])
+            .map(|dir| dir.join(&signature).join(".socket.sock"))
+            .find(|path| path.exists())
+            .map(Hyprland::new)
+    }
+}
+
+impl Compositor for Hyprland {
+    fn name(&self) -> &'static str {
+        "Hyprland"
+    }
+
+    fn focused_output(&self) -> std::io::Result<Option<String>> {
+        let mut stream = connect(&self.socket)?;
+        stream.write_all(b"j/monitors")?;
+
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf)?;
+
+        serde_json::from_slice(&buf)
+            .map(focused)
+            .map_err(std::io::Error::other)
+    }
+}
+
+pub struct Sway {
+    socket: PathBuf,
+}
+
+impl Sway {
+    pub fn new(socket: PathBuf) -> Self {
+        Sway { socket }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        std::env::var("SWAYSOCK")
+            .ok()
+            .map(|path| Sway::new(path.into()))
+    }
+}
+
+impl Compositor for Sway {
+    fn name(&self) -> &'static str {
+        "Sway"
+    }
+
+    fn focused_output(&self) -> std::io::Result<Option<String>> {
+        let mut stream = connect(&self.socket)?;
+
+        let mut msg = SWAY_MAGIC.to_vec();
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(&SWAY_GET_OUTPUTS.to_ne_bytes());
+        stream.write_all(&msg)?;
+
+        let mut header = [0u8; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..6] != SWAY_MAGIC {
+            return Err(std::io::Error::other("Not a sway ipc socket"));
+        }
+
+        let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+        let mut buf = vec![0u8; len as usize];
+        stream.read_exact(&mut buf)?;
+
+        serde_json::from_slice(&buf)
+            .map(focused)
+            .map_err(std::io::Error::other)
+    }
+}
+
+pub fn detect() -> Option<Box<dyn Compositor + Send + Sync>> {
+    if let Some(c) = Hyprland::from_env() {
+        return Some(Box::new(c));
+    }
+
+    if let Some(c) = Sway::from_env() {
+        return Some(Box::new(c));
+    }
+
+    None
+}
+
+static COMPOSITOR: Lazy<Option<Box<dyn Compositor + Send + Sync>>> = Lazy::new(detect);
+
+// None if the compositor is not one we can ask
+pub fn focused_output() -> Option<String> {
+    let compositor = COMPOSITOR.as_ref()?;
+
+    compositor.focused_output().unwrap_or_else(|e| {
+        println!(
+            "Compositor: Failed to ask {} for the focused output: {}",
+            compositor.name(),
+            e
+        );
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::thread::JoinHandle;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    // what the server read and, if it was asked to hold on to it, the open connection
+    type Served = JoinHandle<(Vec<u8>, Option<UnixStream>)>;
+
+    // answers one connection with the reply, or keeps it open without answering if there is none
+    fn serve(request_len: usize, reply: Option<Vec<u8>>) -> (TempDir, PathBuf, Served) {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("ipc.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0u8; request_len];
+            stream.read_exact(&mut request).unwrap();
+
+            match reply {
+                Some(reply) => {
+                    stream.write_all(&reply).unwrap();
+                    (request, None)
+                }
+                None => (request, Some(stream)),
+            }
+        });
+
+        (dir, socket, handle)
+    }
+
+    fn sway_reply(payload: &[u8]) -> Vec<u8> {
+        let mut msg = SWAY_MAGIC.to_vec();
+        msg.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        msg.extend_from_slice(&SWAY_GET_OUTPUTS.to_ne_bytes());
+        msg.extend_from_slice(payload);
+        msg
+    }
+
+    const HYPRLAND_MONITORS: &str = r#"[
+        {"id": 0, "name": "DP-1", "description": "Dell U2720Q", "focused": false},
+        {"id": 1, "name": "eDP-1", "description": "BOE 0x095F", "focused": true}
+    ]"#;
+
+    const SWAY_OUTPUTS: &str = r#"[
+        {"id": 3, "name": "HDMI-A-1", "active": true, "focused": true},
+        {"id": 4, "name": "eDP-1", "active": true, "focused": false}
+    ]"#;
+
+    #[test]
+    fn hyprland_focused() {
+        let (_dir, socket, server) = serve(10, Some(HYPRLAND_MONITORS.into()));
+
+        let res = Hyprland::new(socket).focused_output().unwrap();
+        assert_eq!(res.as_deref(), Some("eDP-1"));
+        assert_eq!(server.join().unwrap().0, b"j/monitors");
+    }
+
+    #[test]
+    fn hyprland_nothing_focused() {
+        let reply = r#"[{"name": "DP-1", "focused": false}]"#;
+        let (_dir, socket, _server) = serve(10, Some(reply.into()));
+
+        assert_eq!(Hyprland::new(socket).focused_output().unwrap(), None);
+    }
+
+    #[test]
+    fn hyprland_malformed() {
+        let (_dir, socket, _server) = serve(10, Some(b"unknown request".to_vec()));
+
+        assert!(Hyprland::new(socket).focused_output().is_err());
+    }
+
+    #[test]
+    fn hyprland_timeout() {
+        let (_dir, socket, _server) = serve(10, None);
+
+        assert!(Hyprland::new(socket).focused_output().is_err());
+    }
+
+    #[test]
+    fn sway_focused() {
+        let (_dir, socket, server) = serve(14, Some(sway_reply(SWAY_OUTPUTS.as_bytes())));
+
+        let res = Sway::new(socket).focused_output().unwrap();
+        assert_eq!(res.as_deref(), Some("HDMI-A-1"));
+
+        let request = server.join().unwrap().0;
+        assert_eq!(&request[..6], SWAY_MAGIC);
+        assert_eq!(request[6..10], 0u32.to_ne_bytes());
+        assert_eq!(request[10..14], SWAY_GET_OUTPUTS.to_ne_bytes());
+    }
+
+    #[test]
+    fn sway_bad_magic() {
+        let mut reply = sway_reply(SWAY_OUTPUTS.as_bytes());
+        reply[..6].copy_from_slice(b"i3-ipx");
+        let (_dir, socket, _server) = serve(14, Some(reply));
+
+        assert!(Sway::new(socket).focused_output().is_err());
+    }
+
+    #[test]
+    fn sway_malformed() {
+        let (_dir, socket, _server) = serve(14, Some(sway_reply(b"{\"outputs\": []}")));
+
+        assert!(Sway::new(socket).focused_output().is_err());
+    }
+
+    #[test]
+    fn sway_truncated() {
+        let mut reply = sway_reply(SWAY_OUTPUTS.as_bytes());
+        reply.truncate(reply.len() - 10);
+        let (_dir, socket, _server) = serve(14, Some(reply));
+
+        assert!(Sway::new(socket).focused_output().is_err());
+    }
+
+    #[test]
+    fn sway_timeout() {
+        let (_dir, socket, _server) = serve(14, None);
+
+        assert!(Sway::new(socket).focused_output().is_err());
+    }
+}
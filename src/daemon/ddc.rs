@@ -0,0 +1,344 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use thiserror::Error;
+
+const DRM_PATH: &str = "/sys/class/drm";
+
+// from linux/i2c-dev.h
+const I2C_SLAVE: u64 = 0x0703;
+// ddc/ci lives on this address of the display's i2c bus
+const DDC_ADDR: u64 = 0x37;
+// the display's address on the bus in 8 bit form, part of every checksum
+const DISPLAY_ADDR: u8 = 0x6e;
+const HOST_ADDR: u8 = 0x51;
+// used instead of HOST_ADDR for the checksum of replies
+const HOST_REPLY_ADDR: u8 = 0x50;
+
+const GET_VCP: u8 = 0x01;
+const GET_VCP_REPLY: u8 = 0x02;
+const SET_VCP: u8 = 0x03;
+const VCP_BRIGHTNESS: u8 = 0x10;
+
+// the minimum delays the ddc/ci spec asks for before reading a reply or sending the next request
+const GET_DELAY: Duration = Duration::from_millis(40);
+const SET_DELAY: Duration = Duration::from_millis(50);
+const RETRIES: usize = 3;
+
+#[derive(Debug, Error)]
+pub enum DdcErr {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Cannot find an i2c bus for the connector {0}")]
+    NoBus(String),
+    #[error("Invalid reply from the display")]
+    InvalidReply,
+    #[error("The display doesn't support VCP code {0:#04x}")]
+    Unsupported(u8),
+}
+
+/// raw transfers on an i2c bus, the display is expected to already be addressed
+pub trait I2cBus {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()>;
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<()>;
+}
+
+/// a /dev/i2c-N device through i2c-dev
+pub struct I2cDev {
+    file: File,
+}
+
+impl I2cDev {
+    pub fn open(path: &Path) -> Result<Self, DdcErr> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        // SAFETY: the fd is valid for the lifetime of file and I2C_SLAVE only takes an address
+        if unsafe { libc::ioctl(file.as_raw_fd(), I2C_SLAVE as _, DDC_ADDR) } < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(I2cDev { file })
+    }
+}
+
+impl I2cBus for I2cDev {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(data)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.file.read_exact(buf)
+    }
+}
+
+fn checksum(init: u8, data: &[u8]) -> u8 {
+    data.iter().fold(init, |acc, b| acc ^ b)
+}
+
+pub struct DdcDisplay<B: I2cBus = I2cDev> {
+    bus: B,
+    // raw maximum of the brightness, read with every get
+    max: Option<u16>,
+}
+
+impl<B: I2cBus> DdcDisplay<B> {
+    pub fn new(bus: B) -> Self {
+        DdcDisplay { bus, max: None }
+    }
+
+    fn send(&mut self, payload: &[u8]) -> Result<(), DdcErr> {
+        let mut msg = vec![HOST_ADDR, 0x80 | payload.len() as u8];
+        msg.extend_from_slice(payload);
+        msg.push(checksum(DISPLAY_ADDR, &msg));
+
+        self.bus.write(&msg)?;
+        Ok(())
+    }
+
+    // returns (current, max)
+    fn get_vcp_once(&mut self, code: u8) -> Result<(u16, u16), DdcErr> {
+        self.send(&[GET_VCP, code])?;
+        std::thread::sleep(GET_DELAY);
+
+        // source address, length, opcode, result, code, type, max, current, checksum
+        let mut reply = [0u8; 11];
+        self.bus.read(&mut reply)?;
+
+        if reply[0] != DISPLAY_ADDR
+            || reply[1] != 0x88
+            || reply[2] != GET_VCP_REPLY
+            || reply[4] != code
+            || checksum(HOST_REPLY_ADDR, &reply[..10]) != reply[10]
+        {
+            return Err(DdcErr::InvalidReply);
+        }
+
+        if reply[3] != 0 {
+            return Err(DdcErr::Unsupported(code));
+        }
+
+        let max = u16::from_be_bytes([reply[6], reply[7]]);
+        let cur = u16::from_be_bytes([reply[8], reply[9]]);
+
+        Ok((cur, max))
+    }
+
+    // displays answer with a null message or garbage when they are busy, so give them a few tries
+    fn get_vcp(&mut self, code: u8) -> Result<(u16, u16), DdcErr> {
+        let mut result = Err(DdcErr::InvalidReply);
+
+        for _ in 0..RETRIES {
+            result = self.get_vcp_once(code);
+            match result {
+                Ok(_) | Err(DdcErr::Unsupported(_)) => break,
+                Err(_) => std::thread::sleep(SET_DELAY),
+            }
+        }
+
+        result
+    }
+
+    fn set_vcp(&mut self, code: u8, val: u16) -> Result<(), DdcErr> {
+        let [hi, lo] = val.to_be_bytes();
+        self.send(&[SET_VCP, code, hi, lo])?;
+        std::thread::sleep(SET_DELAY);
+        Ok(())
+    }
+
+    pub fn get_brightness(&mut self) -> Result<f64, DdcErr> {
+        let (cur, max) = self.get_vcp(VCP_BRIGHTNESS)?;
+        if max == 0 {
+            return Err(DdcErr::InvalidReply);
+        }
+
+        self.max = Some(max);
+        Ok(cur as f64 * 100f64 / max as f64)
+    }
+
+    pub fn set_brightness(&mut self, val: f64) -> Result<(), DdcErr> {
+        let max = match self.max {
+            Some(m) => m,
+            None => {
+                self.get_brightness()?;
+                self.max.unwrap_or(100)
+            }
+        };
+
+        let raw = (val.clamp(0f64, 100f64) * max as f64 / 100f64).round() as u16;
+        self.set_vcp(VCP_BRIGHTNESS, raw)
+    }
+}
+
+// the drm connector links to the i2c adapter of its ddc lines, dp connectors may only have an
+// i2c-N child for the aux channel instead
+fn bus_for_connector(connector: &str) -> Option<PathBuf> {
+    let suffix = format!("-{}", connector);
+
+    let dir = std::fs::read_dir(DRM_PATH)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("card") && n.ends_with(&suffix))
+        })?;
+
+    let name = std::fs::read_link(dir.join("ddc"))
+        .ok()
+        .and_then(|link| link.file_name().map(|n| n.to_os_string()))
+        .or_else(|| {
+            std::fs::read_dir(&dir)
+                .ok()?
+                .flatten()
+                .map(|entry| entry.file_name())
+                .find(|n| n.to_string_lossy().starts_with("i2c-"))
+        })?;
+
+    Some(Path::new("/dev").join(name))
+}
+
+/// opens the display plugged into the given connector, like DP-1 or HDMI-A-1
+pub fn open_connector(connector: &str) -> Result<DdcDisplay, DdcErr> {
+    let path = bus_for_connector(connector).ok_or_else(|| DdcErr::NoBus(connector.to_string()))?;
+    Ok(DdcDisplay::new(I2cDev::open(&path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    // hands out the queued replies in order and keeps what was written
+    #[derive(Default)]
+    struct FakeBus {
+        written: Vec<Vec<u8>>,
+        replies: VecDeque<[u8; 11]>,
+    }
+
+    impl I2cBus for FakeBus {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+            self.written.push(data.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+            let reply = self
+                .replies
+                .pop_front()
+                .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+            buf.copy_from_slice(&reply);
+            Ok(())
+        }
+    }
+
+    fn reply(result: u8, max: u16, cur: u16) -> [u8; 11] {
+        let [max_hi, max_lo] = max.to_be_bytes();
+        let [cur_hi, cur_lo] = cur.to_be_bytes();
+        let mut reply = [
+            DISPLAY_ADDR,
+            0x88,
+            GET_VCP_REPLY,
+            result,
+            VCP_BRIGHTNESS,
+            0x00,
+            max_hi,
+            max_lo,
+            cur_hi,
+            cur_lo,
+            0,
+        ];
+        reply[10] = checksum(HOST_REPLY_ADDR, &reply[..10]);
+        reply
+    }
+
+    fn display(replies: &[[u8; 11]]) -> DdcDisplay<FakeBus> {
+        DdcDisplay::new(FakeBus {
+            written: vec![],
+            replies: replies.iter().copied().collect(),
+        })
+    }
+
+    #[test]
+    fn get_vcp_request_and_reply() {
+        let mut display = display(&[reply(0, 200, 50)]);
+
+        assert_eq!(display.get_brightness().unwrap(), 25f64);
+        assert_eq!(
+            display.bus.written,
+            vec![vec![HOST_ADDR, 0x82, GET_VCP, VCP_BRIGHTNESS, 0xac]]
+        );
+    }
+
+    #[test]
+    fn set_vcp_request() {
+        let mut display = display(&[]);
+        display.max = Some(100);
+
+        display.set_brightness(100f64).unwrap();
+        assert_eq!(
+            display.bus.written,
+            vec![vec![
+                HOST_ADDR,
+                0x84,
+                SET_VCP,
+                VCP_BRIGHTNESS,
+                0x00,
+                0x64,
+                0xcc
+            ]]
+        );
+    }
+
+    #[test]
+    fn get_vcp_retries_garbage() {
+        let mut bad_checksum = reply(0, 100, 30);
+        bad_checksum[10] ^= 0xff;
+        let mut display = display(&[[0u8; 11], bad_checksum, reply(0, 100, 30)]);
+
+        assert_eq!(display.get_brightness().unwrap(), 30f64);
+        assert_eq!(display.bus.written.len(), 3);
+    }
+
+    #[test]
+    fn get_vcp_gives_up_after_retries() {
+        let mut display = display(&[[0xffu8; 11]; RETRIES]);
+
+        assert!(matches!(
+            display.get_brightness(),
+            Err(DdcErr::InvalidReply)
+        ));
+        assert_eq!(display.bus.written.len(), RETRIES);
+    }
+
+    #[test]
+    fn nonzero_result_is_unsupported() {
+        let mut display = display(&[reply(1, 100, 30), reply(0, 100, 30)]);
+
+        assert!(matches!(
+            display.get_brightness(),
+            Err(DdcErr::Unsupported(VCP_BRIGHTNESS))
+        ));
+        // not worth retrying, the display said no
+        assert_eq!(display.bus.written.len(), 1);
+    }
+
+    #[test]
+    fn set_brightness_scales_to_max() {
+        let mut display = display(&[reply(0, 200, 0)]);
+
+        display.set_brightness(50f64).unwrap();
+        display.set_brightness(150f64).unwrap();
+
+        // the first write reads the max, the rest are set requests
+        let values: Vec<_> = display.bus.written[1..]
+            .iter()
+            .map(|msg| u16::from_be_bytes([msg[4], msg[5]]))
+            .collect();
+        assert_eq!(values, vec![100, 200]);
+    }
+}
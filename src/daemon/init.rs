@@ -1,15 +1,13 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use super::renderer::config::default_config_path;
-use super::renderer::window::KeyboardModeWrapper;
-use super::renderer::{app::start_app, config::read_config};
+use super::renderer::config::{default_config_path, set_config, AppConf};
+use super::renderer::monitor::{list_monitors, set_monitors};
+use super::renderer::{app::start_app, config::load_config};
 use super::server;
 use super::structs::DaemonEvt;
-use crate::daemon::notification::denote::Notification;
+use crate::daemon::notification::denote::{NotificationEvt, NotificationReply};
 use crate::utils::{detect_display, DaemonErr};
-use glib::object::Cast;
-use gtk4::prelude::DisplayExt;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 pub fn start_daemon(
@@ -43,13 +41,8 @@ pub fn start_daemon(
         default_config_path()
     };
 
-    let config = Arc::new({
-        let mut c = read_config(&config_path);
-        c.dvoty.window.keyboard_mode = KeyboardModeWrapper {
-            inner: gtk4_layer_shell::KeyboardMode::OnDemand,
-        };
-        c
-    });
+    let config = Arc::new(load_config(&config_path));
+    set_config(config.clone());
 
     let (evt_sender, evt_receiver): (UnboundedSender<DaemonEvt>, UnboundedReceiver<DaemonEvt>) =
         mpsc::unbounded_channel();
@@ -74,28 +67,22 @@ pub fn start_daemon(
     gtk4::init().unwrap();
 
     let display = gtk4::gdk::Display::default().unwrap();
-    let monitors = display.monitors();
-
-    let mut monitor_list = vec![];
-
-    for monitor in (&monitors).into_iter().flatten() {
-        if let Ok(mon) = monitor.downcast::<gtk4::gdk::Monitor>() {
-            monitor_list.push(mon);
-        }
-    }
+    let monitor_list = list_monitors(&display);
+    set_monitors(&monitor_list);
 
     let (notification_sender, notification_receiver) =
-        tokio::sync::mpsc::unbounded_channel::<Notification>();
+        tokio::sync::mpsc::unbounded_channel::<NotificationEvt>();
+    let (notification_reply_sender, notification_reply_receiver) =
+        tokio::sync::mpsc::unbounded_channel::<NotificationReply>();
+    let (config_sender, config_receiver) = tokio::sync::mpsc::unbounded_channel::<Arc<AppConf>>();
 
     // run the server in a different thread
     let evt_sender_clone = evt_sender.clone();
-    let config_clone = config.clone();
-    let len = monitor_list.len();
     std::thread::Builder::new()
         .name("dvvidget server".into())
         .spawn(move || {
             rt.block_on(async {
-                if let Err(e) = server::run_server(&config_path, socket_path, evt_sender.clone(), len, config, notification_sender).await {
+                if let Err(e) = server::run_server(&config_path, socket_path, evt_sender.clone(), config, notification_sender, notification_reply_receiver, config_sender).await {
                     println!("Error running the IPC server: {:?}. Dvvidget will keep running, but the cli won't work", e);
                 }
                 // use tokio::spawn if there are more tasks here, such as information puller
@@ -108,7 +95,8 @@ pub fn start_daemon(
         evt_receiver,
         evt_sender_clone.clone(),
         notification_receiver,
-        config_clone,
+        notification_reply_sender,
+        config_receiver,
         monitor_list,
     );
 
@@ -1,8 +1,13 @@
+pub mod audio;
+pub mod compositor;
+pub mod ddc;
 pub mod info;
 pub mod init;
 pub mod notification;
+pub mod protocol;
 pub mod renderer;
 pub mod server;
 pub mod structs;
+pub mod watcher;
 
 pub use init::start_daemon;
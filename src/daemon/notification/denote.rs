@@ -1,13 +1,19 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use chrono::Local;
-use dbus::channel::MatchingReceiver;
+use dbus::arg::{cast, RefArg};
+use dbus::channel::{MatchingReceiver, Sender};
 use dbus::message::MatchRule;
+use dbus::nonblock::SyncConnection;
 use dbus_crossroads::{Context, Crossroads};
-use tokio::sync::mpsc::UnboundedSender;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
 
+use crate::daemon::renderer::config::{current_config, AppConf, DenoteAction, DenoteRule};
 use crate::utils::shutdown;
 
 const NOTIFICATIONS_INTERFACE: &str = "org.freedesktop.Notifications";
@@ -17,29 +23,225 @@ const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
 type Actions = Vec<String>;
 type Hints = HashMap<String, dbus::arg::Variant<Box<dyn dbus::arg::RefArg + 'static>>>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    pub const ALL: [Urgency; 3] = [Urgency::Low, Urgency::Normal, Urgency::Critical];
+
+    fn from_byte(val: u64) -> Self {
+        match val {
+            0 => Urgency::Low,
+            2 => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        }
+    }
+}
+
+// raw pixels from the image-data hint, (iiibiiay) in the spec
+#[derive(Debug, Clone)]
+pub struct ImageData {
+    pub width: i32,
+    pub height: i32,
+    pub rowstride: i32,
+    pub has_alpha: bool,
+    pub bits_per_sample: i32,
+    pub channels: i32,
+    pub data: Vec<u8>,
+}
+
 // Struct to hold notification data
 #[derive(Debug, Clone)]
 pub struct Notification {
     pub id: u32,
     pub app_name: String,
+    pub app_icon: String,
     pub summary: String,
     pub body: String,
+    pub urgency: Urgency,
+    pub category: Option<String>,
+    pub image_path: Option<String>,
+    pub image_data: Option<ImageData>,
+    pub transient: bool,
+    pub resident: bool,
+    // (key, label) pairs, "default" is invoked by clicking the notification
+    pub actions: Vec<(String, String)>,
+    // milliseconds, -1 means the server decides and 0 means never
+    pub expire_timeout: i32,
     pub timestamp: SystemTime,
 }
 
+// sent from the notification server to the gtk side
+#[derive(Debug, Clone)]
+pub enum NotificationEvt {
+    Notify(Box<Notification>),
+    // only goes to the history, used for do not disturb and suppressed notifications
+    Record(Box<Notification>),
+    Close(u32),
+}
+
+pub static DND: AtomicBool = AtomicBool::new(false);
+
+// reasons of the NotificationClosed signal
+#[derive(Debug, Clone, Copy)]
+pub enum CloseReason {
+    Expired = 1,
+    Dismissed = 2,
+    Closed = 3,
+}
+
+// sent from the gtk side back to the notification server
+#[derive(Debug, Clone)]
+pub enum NotificationReply {
+    Closed(u32, CloseReason),
+    // id, action key, activation token
+    ActionInvoked(u32, String, Option<String>),
+}
+
 // Struct to hold server state
 struct NotificationServer {
     notifications: HashMap<u32, Notification>,
+    timers: HashMap<u32, JoinHandle<()>>,
     next_id: u32,
-    sender: UnboundedSender<Notification>,
+    sender: UnboundedSender<NotificationEvt>,
+    connection: Arc<SyncConnection>,
+}
+
+fn hint_str(hints: &Hints, key: &str) -> Option<String> {
+    hints
+        .get(key)
+        .and_then(|v| v.0.as_str())
+        .map(|s| s.to_string())
+}
+
+fn hint_bool(hints: &Hints, key: &str) -> bool {
+    hints
+        .get(key)
+        .and_then(|v| v.0.as_i64())
+        .is_some_and(|v| v != 0)
+}
+
+// the bytes the last row ends at, None if the sizes make no sense
+// all of it comes from the client, so nothing is trusted not to overflow
+fn image_len(image: &ImageData) -> Option<usize> {
+    if image.width <= 0 || image.height <= 0 || image.rowstride < 0 {
+        return None;
+    }
+
+    let rows = i64::from(image.rowstride).checked_mul(i64::from(image.height) - 1)?;
+    let last = i64::from(image.width).checked_mul(i64::from(image.channels))?;
+
+    usize::try_from(rows.checked_add(last)?).ok()
+}
+
+fn hint_image_data(hints: &Hints) -> Option<ImageData> {
+    // image_data and icon_data are deprecated names of the same hint
+    let value = ["image-data", "image_data", "icon_data"]
+        .iter()
+        .find_map(|key| hints.get(*key))?;
+
+    let field = |index: usize| value.0.as_static_inner(index);
+    let int = |index: usize| {
+        field(index)
+            .and_then(|v| v.as_i64())
+            .and_then(|v| i32::try_from(v).ok())
+    };
+
+    let image = ImageData {
+        width: int(0)?,
+        height: int(1)?,
+        rowstride: int(2)?,
+        has_alpha: field(3).and_then(|v| v.as_i64())? != 0,
+        bits_per_sample: int(4)?,
+        channels: int(5)?,
+        data: cast::<Vec<u8>>(field(6)?)?.clone(),
+    };
+
+    if image.bits_per_sample != 8
+        || !(3..=4).contains(&image.channels)
+        || image_len(&image).is_none_or(|len| image.data.len() < len)
+    {
+        println!("Denote: Ignoring malformed image data");
+        return None;
+    }
+
+    Some(image)
+}
+
+fn rule_matches(rule: &DenoteRule, notification: &Notification) -> bool {
+    rule.app_name
+        .as_ref()
+        .is_none_or(|name| *name == notification.app_name)
+        && rule
+            .summary
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(&notification.summary))
+        && rule
+            .urgency
+            .is_none_or(|urgency| urgency == notification.urgency)
 }
 
 impl NotificationServer {
-    fn new(sender: UnboundedSender<Notification>) -> Self {
+    fn new(sender: UnboundedSender<NotificationEvt>, connection: Arc<SyncConnection>) -> Self {
         NotificationServer {
             notifications: HashMap::new(),
+            timers: HashMap::new(),
             next_id: 1,
             sender,
+            connection,
+        }
+    }
+
+    fn expiry(&self, notification: &Notification) -> Option<Duration> {
+        match notification.expire_timeout {
+            0 => None,
+            t if t > 0 => Some(Duration::from_millis(t as u64)),
+            _ => {
+                let config = current_config();
+                let secs = if notification.urgency == Urgency::Critical {
+                    config.denote.critical_timeout
+                } else {
+                    config.denote.timeout
+                };
+
+                if secs > 0f64 {
+                    Some(Duration::from_secs_f64(secs))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn should_popup(&self, notification: &Notification) -> bool {
+        let dnd = DND.load(Ordering::SeqCst);
+
+        let action = current_config()
+            .denote
+            .rules
+            .iter()
+            .find(|rule| rule_matches(rule, notification))
+            .map(|rule| rule.action);
+
+        match action {
+            Some(DenoteAction::Show) => true,
+            Some(DenoteAction::Silence) => !dnd,
+            Some(DenoteAction::Suppress) => false,
+            // critical notifications bypass do not disturb by default
+            None => !dnd || notification.urgency == Urgency::Critical,
         }
     }
 
@@ -67,12 +269,12 @@ impl NotificationServer {
                  (
                     app_name,
                     replaces_id,
-                    _app_icon,
+                    app_icon,
                     summary,
                     body,
-                    _actions,
-                    _hints,
-                    _expire_timeout,
+                    actions,
+                    hints,
+                    expire_timeout,
                 ): (String, u32, String, String, String, Actions, Hints, i32)| {
                     let mut server_lock = server.lock().unwrap();
                     let notification_id: u32;
@@ -88,17 +290,52 @@ impl NotificationServer {
                     // Create and store the notification
                     let notification = Notification {
                         id: notification_id,
-                        app_name: app_name.clone(),
-                        summary: summary.clone(),
-                        body: body.clone(),
+                        app_name,
+                        app_icon,
+                        summary,
+                        body,
+                        urgency: hints
+                            .get("urgency")
+                            .and_then(|v| v.0.as_u64())
+                            .map_or(Urgency::Normal, Urgency::from_byte),
+                        category: hint_str(&hints, "category"),
+                        image_path: hint_str(&hints, "image-path")
+                            .or_else(|| hint_str(&hints, "image_path")),
+                        image_data: hint_image_data(&hints),
+                        transient: hint_bool(&hints, "transient"),
+                        resident: hint_bool(&hints, "resident"),
+                        actions: actions
+                            .chunks_exact(2)
+                            .map(|pair| (pair[0].clone(), pair[1].clone()))
+                            .collect(),
+                        expire_timeout,
                         timestamp: SystemTime::now(),
                     };
 
+                    if let Some(handle) = server_lock.timers.remove(&notification_id) {
+                        handle.abort();
+                    }
+
+                    if let Some(duration) = server_lock.expiry(&notification) {
+                        let server_clone = server.clone();
+                        let handle = tokio::spawn(async move {
+                            tokio::time::sleep(duration).await;
+                            close_notification(
+                                &server_clone,
+                                notification_id,
+                                CloseReason::Expired,
+                            );
+                        });
+
+                        server_lock.timers.insert(notification_id, handle);
+                    }
+
                     server_lock
                         .notifications
                         .insert(notification_id, notification.clone());
 
-                    handle_notify(server_lock.sender.clone(), notification);
+                    let popup = server_lock.should_popup(&notification);
+                    handle_notify(server_lock.sender.clone(), notification, popup);
 
                     Ok((notification_id,))
                 },
@@ -110,7 +347,13 @@ impl NotificationServer {
                 (),
                 ("capabilities",),
                 |_: &mut Context, _: &mut Arc<Mutex<NotificationServer>>, _: ()| {
-                    let capabilities = vec!["body".to_string(), "body-markup".to_string()];
+                    let capabilities = vec![
+                        "body".to_string(),
+                        "body-markup".to_string(),
+                        "icon-static".to_string(),
+                        "actions".to_string(),
+                        "persistence".to_string(),
+                    ];
                     Ok((capabilities,))
                 },
             );
@@ -120,8 +363,8 @@ impl NotificationServer {
                 "CloseNotification",
                 ("id",),
                 (),
-                |ctx: &mut Context, server: &mut Arc<Mutex<NotificationServer>>, (id,): (u32,)| {
-                    handle_close_notification(ctx, server, id);
+                |_: &mut Context, server: &mut Arc<Mutex<NotificationServer>>, (id,): (u32,)| {
+                    close_notification(server, id, CloseReason::Closed);
                     Ok(())
                 },
             );
@@ -143,49 +386,129 @@ impl NotificationServer {
 
             // NotificationClosed signal
             b.signal::<(u32, u32), _>("NotificationClosed", ("id", "reason"));
+
+            // ActionInvoked signal
+            b.signal::<(u32, String), _>("ActionInvoked", ("id", "action_key"));
+
+            // ActivationToken signal, added in 1.2
+            b.signal::<(u32, String), _>("ActivationToken", ("id", "activation_token"));
         })
     }
 }
 
 // Handle the Notify method call
-fn handle_notify(sender: UnboundedSender<Notification>, notification: Notification) {
+fn handle_notify(
+    sender: UnboundedSender<NotificationEvt>,
+    notification: Notification,
+    popup: bool,
+) {
     // Print notification details
     let time = Local::now().format("%H:%M:%S").to_string();
     println!(
-        "[{}] Notification #{} from {}: {}",
-        time, notification.id, notification.app_name, notification.summary
+        "[{}] Notification #{} from {} ({}): {}",
+        time,
+        notification.id,
+        notification.app_name,
+        notification.urgency.as_str(),
+        notification.summary
     );
     if !notification.body.is_empty() {
         println!("  Body: {}", notification.body);
     }
 
-    sender.send(notification).unwrap_or_else(|e| {
+    let evt = if popup {
+        NotificationEvt::Notify(Box::new(notification))
+    } else {
+        println!("  Silenced, only recorded in the history");
+        NotificationEvt::Record(Box::new(notification))
+    };
+
+    sender.send(evt).unwrap_or_else(|e| {
         println!("Denote: Cannot send notification: {}", e);
     });
 }
 
-// Handle the CloseNotification method call
-fn handle_close_notification(ctx: &mut Context, server: &Arc<Mutex<NotificationServer>>, id: u32) {
-    let mut server_lock = server.lock().unwrap();
+// Remove the notification, emit NotificationClosed and hide the popup if it's still shown
+fn close_notification(server: &Arc<Mutex<NotificationServer>>, id: u32, reason: CloseReason) {
+    let mut server_lock = server.lock().unwrap_or_else(|p| p.into_inner());
+
+    if let Some(handle) = server_lock.timers.remove(&id) {
+        handle.abort();
+    }
+
+    if server_lock.notifications.remove(&id).is_none() {
+        return;
+    }
+
+    println!("Closing notification #{} ({:?})", id, reason);
+
+    emit_signal(
+        &server_lock.connection,
+        "NotificationClosed",
+        (id, reason as u32),
+    );
+
+    // dismissed notifications are already gone from the screen
+    if let CloseReason::Dismissed = reason {
+        return;
+    }
+
+    server_lock
+        .sender
+        .send(NotificationEvt::Close(id))
+        .unwrap_or_else(|e| {
+            println!("Denote: Cannot send close event: {}", e);
+        });
+}
+
+fn emit_signal<A: dbus::arg::AppendAll>(connection: &SyncConnection, name: &str, args: A) {
+    let mut signal = dbus::message::Message::signal(
+        &NOTIFICATIONS_PATH.into(),
+        &NOTIFICATIONS_INTERFACE.into(),
+        &name.into(),
+    );
+    signal.append_all(args);
+
+    if connection.send(signal).is_err() {
+        println!("Denote: Failed to emit {}", name);
+    }
+}
+
+fn handle_action_invoked(
+    server: &Arc<Mutex<NotificationServer>>,
+    id: u32,
+    key: String,
+    token: Option<String>,
+) {
+    let server_lock = server.lock().unwrap_or_else(|p| p.into_inner());
+
+    if !server_lock.notifications.contains_key(&id) {
+        return;
+    }
+
+    println!("Invoking action {} of notification #{}", key, id);
 
-    if server_lock.notifications.remove(&id).is_some() {
-        println!("Closing notification #{}", id);
+    // the token has to arrive before the action so the client can use it right away
+    if let Some(token) = token {
+        emit_signal(&server_lock.connection, "ActivationToken", (id, token));
+    }
 
-        // Emit NotificationClosed signal (reason 3 = closed by CloseNotification call)
-        let reason: u32 = 3;
-        let signal = dbus::message::Message::signal(
-            &NOTIFICATIONS_PATH.into(),
-            &NOTIFICATIONS_INTERFACE.into(),
-            &"NotificationClosed".into(),
-        )
-        .append2(id, reason);
+    emit_signal(&server_lock.connection, "ActionInvoked", (id, key));
+}
 
-        ctx.push_msg(signal);
+fn handle_reply(server: &Arc<Mutex<NotificationServer>>, reply: NotificationReply) {
+    match reply {
+        NotificationReply::Closed(id, reason) => close_notification(server, id, reason),
+        NotificationReply::ActionInvoked(id, key, token) => {
+            handle_action_invoked(server, id, key, token)
+        }
     }
 }
 
 pub async fn start_notification_server(
-    sender: UnboundedSender<Notification>,
+    sender: UnboundedSender<NotificationEvt>,
+    mut reply_receiver: UnboundedReceiver<NotificationReply>,
+    config: Arc<AppConf>,
 ) -> Result<Box<dyn FnOnce()>, Box<dyn std::error::Error>> {
     // Create a new Crossroads instance
     let mut cr = Crossroads::new();
@@ -200,12 +523,17 @@ pub async fn start_notification_server(
         }),
     )));
 
+    DND.store(config.denote.dnd, Ordering::SeqCst);
+
     // Create server state and register interface
-    let server = Arc::new(Mutex::new(NotificationServer::new(sender)));
+    let server = Arc::new(Mutex::new(NotificationServer::new(
+        sender,
+        connection.clone(),
+    )));
     let iface_token = NotificationServer::register_interface(&mut cr);
 
     // Register server state at object path
-    cr.insert(NOTIFICATIONS_PATH, &[iface_token], server);
+    cr.insert(NOTIFICATIONS_PATH, &[iface_token], server.clone());
 
     connection.start_receive(
         MatchRule::new_method_call(),
@@ -224,6 +552,12 @@ pub async fn start_notification_server(
         ));
     });
 
+    let reply_handle = tokio::spawn(async move {
+        while let Some(reply) = reply_receiver.recv().await {
+            handle_reply(&server, reply);
+        }
+    });
+
     // Request the Notifications service name
     connection
         .request_name(NOTIFICATIONS_INTERFACE, false, true, false)
@@ -232,6 +566,7 @@ pub async fn start_notification_server(
     Ok(Box::new(move || {
         println!("shutting down the notification server");
         handle.abort();
+        reply_handle.abort();
         drop(connection);
     }))
 }
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::cache_dir;
+
+use super::denote::{Notification, Urgency};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    // unlike notification ids, these stay unique across restarts
+    pub id: u64,
+    pub app_name: String,
+    pub summary: String,
+    pub body: String,
+    pub urgency: Urgency,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NotificationHistory {
+    next_id: u64,
+    // oldest first
+    pub entries: Vec<HistoryEntry>,
+}
+
+fn history_path() -> PathBuf {
+    let mut path = cache_dir();
+    path.push("notifications");
+    path
+}
+
+impl NotificationHistory {
+    pub fn load() -> Self {
+        let path = history_path();
+        match std::fs::read(&path) {
+            Ok(buf) => bincode::deserialize(&buf).unwrap_or_else(|e| {
+                println!("Notif: Cannot parse the history file, starting over: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let buf = match bincode::serialize(self) {
+            Ok(b) => b,
+            Err(e) => {
+                println!("Notif: Cannot serialize the history: {}", e);
+                return;
+            }
+        };
+
+        std::fs::write(history_path(), buf).unwrap_or_else(|e| {
+            println!("Notif: Cannot write the history file: {}", e);
+        });
+    }
+
+    // returns the id of the new entry
+    pub fn push(&mut self, notification: &Notification, limit: usize) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.entries.push(HistoryEntry {
+            id,
+            app_name: notification.app_name.clone(),
+            summary: notification.summary.clone(),
+            body: notification.body.clone(),
+            urgency: notification.urgency,
+            timestamp: notification.timestamp,
+        });
+
+        if self.entries.len() > limit {
+            let excess = self.entries.len() - limit;
+            self.entries.drain(0..excess);
+        }
+
+        id
+    }
+
+    // replaced notifications update their entry instead of adding a new one
+    pub fn replace(&mut self, id: u64, notification: &Notification) -> bool {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.id == id) {
+            entry.summary = notification.summary.clone();
+            entry.body = notification.body.clone();
+            entry.urgency = notification.urgency;
+            entry.timestamp = notification.timestamp;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.retain(|e| e.id != id);
+    }
+
+    pub fn remove_app(&mut self, app_name: &str) {
+        self.entries.retain(|e| e.app_name != app_name);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // groups ordered by their latest notification, entries newest first
+    pub fn grouped(&self) -> Vec<(String, Vec<&HistoryEntry>)> {
+        let mut result: Vec<(String, Vec<&HistoryEntry>)> = vec![];
+
+        for entry in self.entries.iter().rev() {
+            if let Some((_, group)) = result.iter_mut().find(|(app, _)| *app == entry.app_name) {
+                group.push(entry);
+            } else {
+                result.push((entry.app_name.clone(), vec![entry]));
+            }
+        }
+
+        result
+    }
+}
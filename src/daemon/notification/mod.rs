@@ -1,3 +1,4 @@
 pub mod denote;
+pub mod history;
 
 pub use denote::start_notification_server;
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// both sides open the connection with these, followed by their PROTOCOL_VERSION
+pub const MAGIC: [u8; 4] = *b"DVVG";
+// bump whenever the bincode layout of DaemonCmdClient or DaemonRes changes
+pub const PROTOCOL_VERSION: u16 = 1;
+// commands are a few hundred bytes, anything near this is not a client of ours
+pub const MAX_FRAME_LEN: usize = 4 * 1024 * 1024;
+
+// the request families this build understands
+pub const CAPABILITIES: &[&str] = &[
+    "vol",
+    "mic",
+    "vol-device",
+    "bri",
+    "ddc",
+    "dvoty",
+    "notif",
+    "dnd",
+    "json",
+    "subscribe",
+    "batch",
+    "monitor-names",
+    "monitors",
+    "show-on",
+];
+
+/// sent by the daemon right after its header
+/// the layout has to stay the same across protocol versions so clients can always report it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Handshake {
+    pub daemon_version: String,
+    pub protocol_version: u16,
+    pub capabilities: Vec<String>,
+}
+
+impl Handshake {
+    pub fn current() -> Self {
+        Handshake {
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+pub async fn write_header<W: AsyncWrite + Unpin>(writer: &mut W) -> std::io::Result<()> {
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    writer.write_all(&buf).await
+}
+
+// returns the version of the other side, None if it doesn't speak this protocol at all
+pub async fn read_header<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Option<u16>> {
+    let mut buf = [0u8; 6];
+    reader.read_exact(&mut buf).await?;
+
+    if buf[..4] != MAGIC {
+        return Ok(None);
+    }
+
+    Ok(Some(u16::from_le_bytes([buf[4], buf[5]])))
+}
+
+// everything after the headers is sent in the following fashion:
+/**
+ * +----------------------------+-------------+
+ * | u32 size in littlen endian | actual data |
+ * +----------------------------+-------------+
+ */
+pub async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    data: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_le_bytes()).await?;
+    writer.write_all(data).await
+}
+
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf).await?;
+
+    // the length comes from the other side, check it before allocating anything
+    let len = u32::from_le_bytes(len_buf) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "frame of {} bytes is over the {} byte limit",
+                len, MAX_FRAME_LEN
+            ),
+        ));
+    }
+
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).await?;
+
+    Ok(buf)
+}
@@ -1,14 +1,18 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
-use crate::daemon::notification::denote::Notification;
+use crate::daemon::notification::denote::DND;
+use crate::daemon::notification::denote::{NotificationEvt, NotificationReply};
 use crate::daemon::structs::DaemonCmdType;
+use crate::daemon::structs::DaemonEvent;
 use crate::daemon::structs::DaemonEvt;
 use crate::daemon::structs::DaemonRes;
+use crate::utils::publish_event;
 use crate::utils::DaemonErr;
 use crate::utils::DisplayBackend;
 use crate::utils::ExitType;
@@ -16,9 +20,10 @@ use gio::ApplicationFlags;
 use gtk4::gdk;
 use gtk4::prelude::*;
 use gtk4::Application;
-use gtk4::CssProvider;
+use gtk4::ApplicationWindow;
 use gtk4::Window;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use std::process::Command;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
@@ -26,45 +31,110 @@ use tokio::sync::mpsc::UnboundedSender;
 use super::bri::create_bri_osd;
 use super::bri::handle_bri_cmd;
 use super::bri::BriContext;
-use super::config::AppConf;
+use super::config::BriCmdProvider;
+use super::config::{current_config, AppConf};
+use super::css::CssContext;
 use super::dvoty::create_dvoty;
-use super::dvoty::event::CURRENT_IDS;
+use super::dvoty::event::reset_current_ids;
 use super::dvoty::handle_dvoty_cmd;
 use super::dvoty::DvotyContext;
+use super::monitor::{list_monitors, set_monitors, MonitorInfo, MONITORS};
+use super::notif::create_notif_popup;
+use super::notif::handle_notif_cmd;
+use super::notif::NotifContext;
+use super::notif_center::create_notif_center;
 use super::vol::create_sound_osd;
 use super::vol::handle_vol_cmd;
+use super::vol::handle_vol_device_cmd;
 use super::vol::VolContext;
+use super::vol::VolKind;
 
 #[repr(C)]
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Widget {
     Volume = 0,
     Brightness = 1,
     Dvoty = 2,
+    Notification = 3,
+    NotificationCenter = 4,
+    Microphone = 5,
 }
 
 pub struct AppContext {
     pub vol: VolContext,
+    pub mic: VolContext,
     pub bri: BriContext,
     pub dvoty: DvotyContext,
-    pub monitor_count: usize,
+    pub notif: NotifContext,
+    // the config the windows were last built with
+    pub config: Arc<AppConf>,
+    // the stylesheets, reloaded in place when their files change
+    pub css: CssContext,
 }
 
 pub static IS_GUI_SHUT: AtomicBool = AtomicBool::new(false);
 
 impl AppContext {
-    pub fn from_config(config: &Arc<AppConf>, monitor_count: usize) -> Self {
-        let vol = VolContext::from_config(config, monitor_count);
-        let bri = BriContext::from_config(config, monitor_count);
+    pub fn from_config(
+        config: &Arc<AppConf>,
+        monitor_list: &[gdk::Monitor],
+        notification_reply_sender: UnboundedSender<NotificationReply>,
+        css_sender: UnboundedSender<notify::Result<notify::Event>>,
+    ) -> Self {
+        let monitor_count = monitor_list.len();
+        let vol = VolContext::from_config(config, VolKind::Sink);
+        let mic = VolContext::from_config(config, VolKind::Source);
+        let bri = BriContext::from_config(config, monitor_list);
         let dvoty = DvotyContext::from_config(config, monitor_count);
+        let notif = NotifContext::from_config(config, notification_reply_sender);
 
         AppContext {
             vol,
+            mic,
             bri,
             dvoty,
-            monitor_count,
+            notif,
+            config: config.clone(),
+            css: CssContext::new(css_sender),
         }
     }
+
+    // most of the config is read on every command, this is what gets copied out of it
+    pub fn reload(&mut self, config: &Arc<AppConf>, monitors: &[gdk::Monitor]) {
+        self.vol.max_vol = config.vol.max_vol;
+        self.mic.max_vol = config.mic.max_vol;
+
+        // only ddc keeps state of its own per monitor
+        if matches!(self.config.bri.run_cmd, BriCmdProvider::Ddc)
+            != matches!(config.bri.run_cmd, BriCmdProvider::Ddc)
+        {
+            for tasks in self.bri.bri_tasks_window.drain(..) {
+                tasks.into_values().for_each(|handle| handle.abort());
+            }
+            self.bri = BriContext::from_config(config, monitors);
+        }
+
+        self.config = config.clone();
+    }
+
+    // the monitors from position on are new or moved, the ones before it keep everything
+    pub fn reset_monitors(
+        &mut self,
+        config: &AppConf,
+        position: usize,
+        old: &[MonitorInfo],
+        monitors: &[gdk::Monitor],
+    ) {
+        let old_ids = old.iter().map(MonitorInfo::id).collect::<Vec<String>>();
+        let rebuilt = &old_ids[position.min(old_ids.len())..];
+
+        self.vol.reset_monitors(rebuilt);
+        self.mic.reset_monitors(rebuilt);
+        self.bri
+            .reset_monitors(config, position, &old_ids, monitors);
+        self.dvoty.reset_monitors(position, monitors.len(), rebuilt);
+        reset_current_ids(position, monitors.len());
+    }
 }
 
 pub fn register_widget(widget: Widget, id: u32) {
@@ -90,41 +160,220 @@ lazy_static! {
 fn get_windows(
     widget: Widget,
     guard: &HashMap<Widget, Vec<u32>>,
-    app: &Rc<Application>,
+    app: &Application,
 ) -> Vec<Window> {
+    // widgets that are disabled in the config never register any window
     guard
         .get(&widget)
-        .unwrap()
-        .iter()
-        .map(|id| app.window_by_id(*id).unwrap())
-        .collect::<Vec<Window>>()
+        .map(|ids| {
+            ids.iter()
+                .map(|id| app.window_by_id(*id).unwrap())
+                .collect::<Vec<Window>>()
+        })
+        .unwrap_or_default()
+}
+
+// the windows of an enabled widget, once every monitor is known to have one
+fn widget_windows(
+    widget: Widget,
+    app: &Application,
+    monitors: &[usize],
+) -> Result<Vec<Window>, DaemonErr> {
+    let guard = match WINDOWS.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let windows = get_windows(widget, &guard, app);
+    if windows.is_empty() {
+        return Err(DaemonErr::WidgetDisabled(widget));
+    }
+
+    if let Some(monitor) = monitors.iter().find(|m| **m >= windows.len()) {
+        return Err(DaemonErr::UnknownMonitor(*monitor));
+    }
+
+    Ok(windows)
+}
+
+// the widgets whose visibility subscribers can follow
+const SUBSCRIBED_WIDGETS: [Widget; 5] = [
+    Widget::Volume,
+    Widget::Microphone,
+    Widget::Brightness,
+    Widget::Dvoty,
+    Widget::NotificationCenter,
+];
+
+// the parts of the state subscribers get told about
+struct StateSnapshot {
+    vol: (f64, bool),
+    mic: (f64, bool),
+    bri: Vec<f64>,
+    dnd: bool,
+}
+
+impl StateSnapshot {
+    fn take(context: &AppContext) -> Self {
+        StateSnapshot {
+            vol: (context.vol.cur_vol, context.vol.is_muted),
+            mic: (context.mic.cur_vol, context.mic.is_muted),
+            bri: context.bri.cur_bri.clone(),
+            dnd: DND.load(Ordering::SeqCst),
+        }
+    }
+
+    // whatever differs from before, or everything without it
+    fn events(&self, config: &AppConf, before: Option<&StateSnapshot>) -> Vec<DaemonEvent> {
+        let mut res = vec![];
+
+        if config.vol.enable && before.is_none_or(|b| b.vol != self.vol) {
+            res.push(DaemonEvent::Vol {
+                volume: self.vol.0,
+                muted: self.vol.1,
+            });
+        }
+
+        if config.mic.enable && before.is_none_or(|b| b.mic != self.mic) {
+            res.push(DaemonEvent::Mic {
+                volume: self.mic.0,
+                muted: self.mic.1,
+            });
+        }
+
+        if config.bri.enable {
+            for (monitor, brightness) in self.bri.iter().enumerate() {
+                if before.is_none_or(|b| b.bri.get(monitor) != Some(brightness)) {
+                    res.push(DaemonEvent::Bri {
+                        monitor,
+                        brightness: *brightness,
+                    });
+                }
+            }
+        }
+
+        if before.is_none_or(|b| b.dnd != self.dnd) {
+            res.push(DaemonEvent::Dnd { enabled: self.dnd });
+        }
+
+        res
+    }
+
+    fn publish(&self, config: &AppConf, before: Option<&StateSnapshot>) {
+        self.events(config, before)
+            .into_iter()
+            .for_each(publish_event);
+    }
 }
 
+fn visibility_events(app: &Application) -> Vec<DaemonEvent> {
+    let guard = match WINDOWS.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let mut res = vec![];
+    for widget in SUBSCRIBED_WIDGETS {
+        for (monitor, window) in get_windows(widget, &guard, app).iter().enumerate() {
+            res.push(DaemonEvent::Visible {
+                widget,
+                monitor,
+                visible: window.is_visible(),
+            });
+        }
+    }
+
+    res
+}
+
+// commands that answer later take the reply out of `reply`
 fn process_evt(
     evt: DaemonCmdType,
     app: Rc<Application>,
     sender: UnboundedSender<DaemonEvt>,
-    config: Arc<AppConf>,
     app_context: Rc<RefCell<AppContext>>,
     monitors: Vec<usize>,
     id: Option<uuid::Uuid>,
+    reply: &mut Option<UnboundedSender<DaemonRes>>,
 ) -> Result<DaemonRes, DaemonErr> {
+    let config = app_context.borrow().config.clone();
+
     match evt {
         DaemonCmdType::ShutDown => {
             app.quit();
         }
 
-        DaemonCmdType::Vol(evt) => {
-            let guard = match WINDOWS.lock() {
-                Ok(g) => g,
-                Err(poisoned) => poisoned.into_inner(),
+        DaemonCmdType::Batch(cmds) => {
+            let cmds = cmds
+                .into_iter()
+                .map(|cmd| (cmd, monitors.clone()))
+                .collect();
+            return process_evt(
+                DaemonCmdType::ResolvedBatch(cmds),
+                app,
+                sender,
+                app_context,
+                monitors,
+                id,
+                reply,
+            );
+        }
+
+        // every command runs before gtk gets to draw anything, so there is no flicker in between.
+        // commands answering later, like ddc writes, can't hold the batch back and only log failures
+        DaemonCmdType::ResolvedBatch(cmds) => {
+            let results = cmds
+                .into_iter()
+                .map(|(cmd, monitors)| {
+                    process_evt(
+                        cmd,
+                        app.clone(),
+                        sender.clone(),
+                        app_context.clone(),
+                        monitors,
+                        id,
+                        &mut None,
+                    )
+                    .unwrap_or_else(|e| DaemonRes::Failure(e.into()))
+                })
+                .collect();
+
+            return Ok(DaemonRes::Batch(results));
+        }
+
+        // the current state only goes to the new subscriber, one response per event, the others
+        // have it already
+        DaemonCmdType::Subscribe => {
+            if let Some(reply) = reply.take() {
+                let events = StateSnapshot::take(&app_context.borrow())
+                    .events(&config, None)
+                    .into_iter()
+                    .chain(visibility_events(&app));
+
+                for evt in events {
+                    if let Err(e) = reply.send(DaemonRes::Event(evt)) {
+                        println!("Err sending the state to a subscriber: {:?}", e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        DaemonCmdType::ListMonitors => {
+            let monitors = match MONITORS.read() {
+                Ok(g) => g.clone(),
+                Err(poisoned) => poisoned.into_inner().clone(),
             };
 
+            return Ok(DaemonRes::Monitors(monitors));
+        }
+
+        DaemonCmdType::Vol(evt) => {
             let vol_context = &mut app_context.borrow_mut().vol;
 
             let result = handle_vol_cmd(
                 evt,
-                &get_windows(Widget::Volume, &guard, &app),
+                &widget_windows(Widget::Volume, &app, &monitors)?,
                 sender,
                 vol_context,
                 config,
@@ -134,37 +383,47 @@ fn process_evt(
             return Ok(result);
         }
 
-        DaemonCmdType::Bri(evt) => {
-            let guard = match WINDOWS.lock() {
-                Ok(g) => g,
-                Err(poisoned) => poisoned.into_inner(),
-            };
+        DaemonCmdType::Mic(evt) => {
+            let mic_context = &mut app_context.borrow_mut().mic;
+
+            let result = handle_vol_cmd(
+                evt,
+                &widget_windows(Widget::Microphone, &app, &monitors)?,
+                sender,
+                mic_context,
+                config,
+                monitors,
+            )?;
 
+            return Ok(result);
+        }
+
+        DaemonCmdType::VolDevice(device, evt) => {
+            return Ok(handle_vol_device_cmd(device, evt, config));
+        }
+
+        DaemonCmdType::Bri(evt) => {
             let bri_context = &mut app_context.borrow_mut().bri;
 
             let result = handle_bri_cmd(
                 evt,
-                &get_windows(Widget::Brightness, &guard, &app),
+                &widget_windows(Widget::Brightness, &app, &monitors)?,
                 sender,
                 bri_context,
                 config,
                 monitors,
+                reply,
             )?;
 
             return Ok(result);
         }
 
         DaemonCmdType::Dvoty(evt) => {
-            let guard = match WINDOWS.lock() {
-                Ok(g) => g,
-                Err(poisoned) => poisoned.into_inner(),
-            };
-
             let dvoty_context = &mut app_context.borrow_mut().dvoty;
 
             let result = handle_dvoty_cmd(
                 evt,
-                &get_windows(Widget::Dvoty, &guard, &app),
+                &widget_windows(Widget::Dvoty, &app, &monitors)?,
                 sender,
                 dvoty_context,
                 config,
@@ -174,6 +433,22 @@ fn process_evt(
 
             return Ok(result);
         }
+
+        DaemonCmdType::Notif(evt) => {
+            let notif_context = &mut app_context.borrow_mut().notif;
+
+            let result = handle_notif_cmd(
+                evt,
+                &widget_windows(Widget::Notification, &app, &monitors)?,
+                &widget_windows(Widget::NotificationCenter, &app, &monitors)?,
+                sender,
+                notif_context,
+                config,
+                monitors,
+            )?;
+
+            return Ok(result);
+        }
     }
 
     Ok(DaemonRes::Success)
@@ -192,6 +467,7 @@ fn send_res(sender: Option<UnboundedSender<DaemonRes>>, res: DaemonRes) {
 #[derive(Hash, PartialEq, Eq)]
 pub enum VolBriTaskTypeWindow {
     AwaitClose,
+    MurphValue,
 }
 
 #[derive(Hash, PartialEq, Eq)]
@@ -202,26 +478,201 @@ pub enum VolBriTaskType {
 fn activate(
     backend: DisplayBackend,
     app: &gtk4::Application,
-    config: Arc<AppConf>,
     sender: UnboundedSender<DaemonEvt>,
     monitors: Vec<gdk::Monitor>,
     app_context: Rc<RefCell<AppContext>>,
 ) {
-    let css = CssProvider::new();
-    css.load_from_path(&config.general.css_path);
-    gtk4::style_context_add_provider_for_display(
-        &gdk::Display::default().expect("Cannot open display"),
-        &css,
-        gtk4::STYLE_PROVIDER_PRIORITY_USER,
-    );
+    {
+        let mut context = app_context.borrow_mut();
+        let config = context.config.clone();
+        context.css.apply(&config);
+    }
+
+    for (ind, monitor) in monitors.iter().enumerate() {
+        create_monitor_windows(
+            backend,
+            app,
+            sender.clone(),
+            ind,
+            monitor,
+            app_context.clone(),
+        );
+    }
+
+    let app = app.clone();
+    gdk::Display::default()
+        .expect("Cannot open display")
+        .monitors()
+        .connect_items_changed(move |_, position, removed, added| {
+            println!(
+                "Monitors changed: {} removed and {} added at {}",
+                removed, added, position
+            );
+            monitors_changed(
+                backend,
+                &app,
+                sender.clone(),
+                position as usize,
+                app_context.clone(),
+            );
+        });
+}
+
+fn watch_visibility(widget: Widget, monitor: usize, window: &ApplicationWindow) {
+    window.connect_visible_notify(move |window| {
+        publish_event(DaemonEvent::Visible {
+            widget,
+            monitor,
+            visible: window.is_visible(),
+        })
+    });
+}
+
+// the widgets with a window on every monitor, in the order they are created
+const MONITOR_WIDGETS: [Widget; 6] = [
+    Widget::Volume,
+    Widget::Microphone,
+    Widget::Brightness,
+    Widget::Dvoty,
+    Widget::Notification,
+    Widget::NotificationCenter,
+];
+
+// the windows are appended, so they land at the monitor's index as long as they are created in
+// monitor order
+fn create_widget_window(
+    widget: Widget,
+    backend: DisplayBackend,
+    app: &gtk4::Application,
+    sender: UnboundedSender<DaemonEvt>,
+    ind: usize,
+    monitor: &gdk::Monitor,
+    app_context: Rc<RefCell<AppContext>>,
+) {
+    let config = app_context.borrow().config.clone();
+    let window = match widget {
+        Widget::Volume => create_sound_osd(backend, app, config, monitor, VolKind::Sink),
+        Widget::Microphone if config.mic.enable => {
+            create_sound_osd(backend, app, config, monitor, VolKind::Source)
+        }
+        Widget::Brightness => {
+            let cur_bri = app_context.borrow().bri.cur_bri[ind];
+            create_bri_osd(backend, app, config, monitor, cur_bri)
+        }
+        Widget::Dvoty => create_dvoty(backend, app, config, sender, ind, monitor, app_context),
+        Widget::Notification if config.denote.enable => {
+            create_notif_popup(backend, app, config, monitor)
+        }
+        Widget::NotificationCenter if config.denote.enable => {
+            create_notif_center(backend, app, config, sender, monitor)
+        }
+        _ => return,
+    };
+
+    window.add_css_class(&MonitorInfo::from_monitor(monitor).css_class());
+
+    if SUBSCRIBED_WIDGETS.contains(&widget) {
+        watch_visibility(widget, ind, &window);
+    }
+}
+
+fn create_monitor_windows(
+    backend: DisplayBackend,
+    app: &gtk4::Application,
+    sender: UnboundedSender<DaemonEvt>,
+    ind: usize,
+    monitor: &gdk::Monitor,
+    app_context: Rc<RefCell<AppContext>>,
+) {
+    for widget in MONITOR_WIDGETS {
+        create_widget_window(
+            widget,
+            backend,
+            app,
+            sender.clone(),
+            ind,
+            monitor,
+            app_context.clone(),
+        );
+    }
+}
+
+// the widgets whose windows look different with the new config
+fn changed_widgets(old: &AppConf, new: &AppConf) -> Vec<Widget> {
+    let mut res = vec![];
+
+    if old.vol.enable != new.vol.enable || old.vol.window != new.vol.window {
+        res.push(Widget::Volume);
+    }
+    if old.mic.enable != new.mic.enable || old.mic.window != new.mic.window {
+        res.push(Widget::Microphone);
+    }
+    if old.bri.enable != new.bri.enable || old.bri.window != new.bri.window {
+        res.push(Widget::Brightness);
+    }
+    if old.dvoty.enable != new.dvoty.enable || old.dvoty.window != new.dvoty.window {
+        res.push(Widget::Dvoty);
+    }
+    if old.denote.enable != new.denote.enable || old.denote.window != new.denote.window {
+        res.push(Widget::Notification);
+    }
+    if old.denote.enable != new.denote.enable
+        || old.denote.center_window != new.denote.center_window
+    {
+        res.push(Widget::NotificationCenter);
+    }
+
+    res
+}
+
+fn destroy_windows(app: &gtk4::Application, ids: Vec<u32>) {
+    for id in ids {
+        if let Some(window) = app.window_by_id(id) {
+            window.destroy();
+        }
+    }
+}
+
+fn rebuild_widget(
+    widget: Widget,
+    backend: DisplayBackend,
+    app: &gtk4::Application,
+    sender: UnboundedSender<DaemonEvt>,
+    monitors: &[gdk::Monitor],
+    app_context: Rc<RefCell<AppContext>>,
+) {
+    let stale = match WINDOWS.lock() {
+        Ok(mut g) => g.remove(&widget),
+        Err(poisoned) => poisoned.into_inner().remove(&widget),
+    };
+    destroy_windows(app, stale.unwrap_or_default());
+
+    {
+        let mut context = app_context.borrow_mut();
+        let ids = match MONITORS.read() {
+            Ok(g) => g.iter().map(MonitorInfo::id).collect::<Vec<String>>(),
+            Err(poisoned) => poisoned.into_inner().iter().map(MonitorInfo::id).collect(),
+        };
+        match widget {
+            Widget::Volume => context.vol.reset_monitors(&ids),
+            Widget::Microphone => context.mic.reset_monitors(&ids),
+            Widget::Brightness => {
+                let config = context.config.clone();
+                context.bri.reset_monitors(&config, 0, &ids, monitors);
+            }
+            Widget::Dvoty => {
+                context.dvoty.reset_monitors(0, monitors.len(), &ids);
+                reset_current_ids(0, monitors.len());
+            }
+            Widget::Notification | Widget::NotificationCenter => {}
+        }
+    }
 
     for (ind, monitor) in monitors.iter().enumerate() {
-        create_sound_osd(backend, app, config.clone(), monitor);
-        create_bri_osd(backend, app, config.clone(), monitor);
-        create_dvoty(
+        create_widget_window(
+            widget,
             backend,
             app,
-            config.clone(),
             sender.clone(),
             ind,
             monitor,
@@ -230,23 +681,100 @@ fn activate(
     }
 }
 
+// everything else keeps running as it is, dvoty results and notifications included
+fn reload_config(
+    backend: DisplayBackend,
+    app: &gtk4::Application,
+    config: Arc<AppConf>,
+    sender: UnboundedSender<DaemonEvt>,
+    app_context: Rc<RefCell<AppContext>>,
+) {
+    let monitors = list_monitors(&gdk::Display::default().expect("Cannot open display"));
+    let old = app_context.borrow().config.clone();
+
+    app_context.borrow_mut().reload(&config, &monitors);
+    app_context.borrow_mut().css.apply(&config);
+
+    for widget in changed_widgets(&old, &config) {
+        println!("Config: Rebuilding the {:?} windows", widget);
+        rebuild_widget(
+            widget,
+            backend,
+            app,
+            sender.clone(),
+            &monitors,
+            app_context.clone(),
+        );
+    }
+}
+
+// gdk keeps the monitor list in order, so everything before position is untouched and only the
+// windows from there on are torn down and rebuilt
+fn monitors_changed(
+    backend: DisplayBackend,
+    app: &gtk4::Application,
+    sender: UnboundedSender<DaemonEvt>,
+    position: usize,
+    app_context: Rc<RefCell<AppContext>>,
+) {
+    let config = app_context.borrow().config.clone();
+    let monitors = list_monitors(&gdk::Display::default().expect("Cannot open display"));
+    let before = StateSnapshot::take(&app_context.borrow());
+
+    let stale = {
+        let mut guard = match WINDOWS.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        guard
+            .values_mut()
+            .flat_map(|ids| ids.split_off(position.min(ids.len())))
+            .collect::<Vec<u32>>()
+    };
+
+    destroy_windows(app, stale);
+
+    let old = match MONITORS.read() {
+        Ok(g) => g.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    set_monitors(&monitors);
+
+    app_context
+        .borrow_mut()
+        .reset_monitors(&config, position, &old, &monitors);
+    // the stylesheets of monitors that came or went
+    app_context.borrow_mut().css.apply(&config);
+
+    for (ind, monitor) in monitors.iter().enumerate().skip(position) {
+        create_monitor_windows(
+            backend,
+            app,
+            sender.clone(),
+            ind,
+            monitor,
+            app_context.clone(),
+        );
+    }
+
+    StateSnapshot::take(&app_context.borrow()).publish(&config, Some(&before));
+}
+
 pub fn start_app(
     backend: DisplayBackend,
     evt_receiver: UnboundedReceiver<DaemonEvt>,
     evt_sender: UnboundedSender<DaemonEvt>,
-    notification_receiver: UnboundedReceiver<Notification>,
-    config: Arc<AppConf>,
+    notification_receiver: UnboundedReceiver<NotificationEvt>,
+    notification_reply_sender: UnboundedSender<NotificationReply>,
+    config_receiver: UnboundedReceiver<Arc<AppConf>>,
     monitor_list: Vec<gdk::Monitor>,
 ) {
     super::dvoty::app_launcher::DESKTOP_FILES
         .set(Arc::new(Mutex::new(vec![])))
         .unwrap();
 
-    let mut ids = vec![];
-    for _ in 0..monitor_list.len() {
-        ids.push(Arc::new(Mutex::new(uuid::Uuid::new_v4())));
-    }
-    CURRENT_IDS.set(ids).unwrap();
+    reset_current_ids(0, monitor_list.len());
 
     #[cfg(not(debug_assertions))]
     let name = Some("org.dvida.dvvidgets");
@@ -259,19 +787,26 @@ pub fn start_app(
         ApplicationFlags::NON_UNIQUE | ApplicationFlags::ALLOW_REPLACEMENT,
     ));
 
+    let (css_sender, css_receiver) =
+        tokio::sync::mpsc::unbounded_channel::<notify::Result<notify::Event>>();
+
     let context = Rc::new(RefCell::new(AppContext::from_config(
-        &config,
-        monitor_list.len(),
+        &current_config(),
+        &monitor_list,
+        notification_reply_sender,
+        css_sender,
     )));
 
+    watch_css(css_receiver, context.clone());
+
     if let Err(e) = init_gtk_async(
+        backend,
         evt_receiver,
         evt_sender.clone(),
         app.clone(),
-        config.clone(),
-        &monitor_list,
         context.clone(),
         notification_receiver,
+        config_receiver,
     ) {
         println!("Err handling command: {:?}", e);
     }
@@ -280,7 +815,6 @@ pub fn start_app(
         activate(
             backend,
             app,
-            config.clone(),
             evt_sender.clone(),
             monitor_list.clone(),
             context.clone(),
@@ -290,18 +824,67 @@ pub fn start_app(
     app.run_with_args(&[""]);
 }
 
-fn handle_notification(notification: Notification) {
-    println!("{:?}", notification);
+const CSS_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+// runs next to the gtk main loop for as long as the daemon does
+fn watch_css(
+    mut receiver: UnboundedReceiver<notify::Result<notify::Event>>,
+    app_context: Rc<RefCell<AppContext>>,
+) {
+    glib::MainContext::default().spawn_local(async move {
+        while let Some(res) = receiver.recv().await {
+            let Ok(evt) = res else {
+                continue;
+            };
+            if !app_context.borrow().css.is_css_change(&evt) {
+                continue;
+            }
+
+            // wait for the editor to finish writing, then take everything it did at once
+            glib::timeout_future(CSS_SETTLE_TIME).await;
+
+            let mut paths = evt.paths;
+            while let Ok(res) = receiver.try_recv() {
+                if let Ok(evt) = res {
+                    paths.extend(evt.paths);
+                }
+            }
+
+            app_context.borrow().css.reload(&paths);
+        }
+    });
+}
+
+fn handle_notification(
+    evt: NotificationEvt,
+    app: Rc<Application>,
+    sender: UnboundedSender<DaemonEvt>,
+    config: Arc<AppConf>,
+    app_context: Rc<RefCell<AppContext>>,
+) {
+    let guard = match WINDOWS.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    super::notif::handle_notification(
+        evt,
+        &get_windows(Widget::Notification, &guard, &app),
+        &get_windows(Widget::NotificationCenter, &guard, &app),
+        sender,
+        &mut app_context.borrow_mut().notif,
+        config,
+    );
 }
 
 pub fn init_gtk_async(
+    backend: DisplayBackend,
     mut evt_receiver: UnboundedReceiver<DaemonEvt>,
     evt_sender: UnboundedSender<DaemonEvt>,
     app: Rc<Application>,
-    config: Arc<AppConf>,
-    _monitor_list: &[gdk::Monitor],
     app_context: Rc<RefCell<AppContext>>,
-    mut notification_receiver: UnboundedReceiver<Notification>,
+    mut notification_receiver: UnboundedReceiver<NotificationEvt>,
+    mut config_receiver: UnboundedReceiver<Arc<AppConf>>,
 ) -> Result<(), DaemonErr> {
     glib::MainContext::default().spawn_local(async move {
         loop {
@@ -326,14 +909,25 @@ pub fn init_gtk_async(
                 }
 
                 Some(evt) = evt_receiver.recv() => {
-                    match process_evt(evt.evt, app.clone(), evt_sender.clone(), config.clone(), app_context.clone(), evt.monitors, evt.uuid) {
-                        Err(e) => send_res(evt.sender, DaemonRes::Failure(format!("{:?}", e))),
-                        Ok(res) => send_res(evt.sender, res),
+                    let config = app_context.borrow().config.clone();
+                    let before = StateSnapshot::take(&app_context.borrow());
+
+                    let mut reply = evt.sender;
+                    match process_evt(evt.evt, app.clone(), evt_sender.clone(), app_context.clone(), evt.monitors, evt.uuid, &mut reply) {
+                        Err(e) => send_res(reply, DaemonRes::Failure(e.into())),
+                        Ok(res) => send_res(reply, res),
                     }
+
+                    StateSnapshot::take(&app_context.borrow()).publish(&config, Some(&before));
+                }
+
+                Some(evt) = notification_receiver.recv() => {
+                    let config = app_context.borrow().config.clone();
+                    handle_notification(evt, app.clone(), evt_sender.clone(), config, app_context.clone());
                 }
 
-                Some(notification) = notification_receiver.recv() => {
-                    handle_notification(notification);
+                Some(config) = config_receiver.recv() => {
+                    reload_config(backend, &app, config, evt_sender.clone(), app_context.clone());
                 }
             }
         }
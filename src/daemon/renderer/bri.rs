@@ -1,15 +1,16 @@
 use std::collections::HashMap;
 use std::process::Command;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
 
+use crate::daemon::ddc::{self, DdcDisplay};
 use crate::daemon::structs::{Bri, DaemonEvt, DaemonRes};
 use crate::utils::{self, DisplayBackend};
 use crate::{daemon::structs::DaemonCmdType, utils::DaemonErr};
 
-use super::app::{VolBriTaskType, VolBriTaskTypeWindow};
+use super::app::VolBriTaskTypeWindow;
 use super::config::{AppConf, BriCmdProvider};
-use super::{app::register_widget, window};
+use super::{app::register_widget, monitor::MonitorInfo, window};
 use gtk4::{
     prelude::*, Adjustment, Application, ApplicationWindow, Box, Image, Label, Scale, Window,
 };
@@ -17,41 +18,299 @@ use tokio::sync::mpsc::UnboundedSender;
 use tokio::task::JoinHandle;
 
 pub struct BriContext {
-    pub cur_bri: f64,
+    // per monitor, all the same unless the provider controls each monitor on its own
+    pub cur_bri: Vec<f64>,
+    // one per monitor with the ddc provider, empty otherwise
+    pub displays: Vec<Option<DdcWorker>>,
     pub bri_tasks_window: Vec<HashMap<VolBriTaskTypeWindow, JoinHandle<()>>>,
-    pub bri_tasks: HashMap<VolBriTaskType, JoinHandle<()>>,
 }
 
 impl BriContext {
-    pub fn from_config(config: &Arc<AppConf>, monitor_count: usize) -> Self {
-        let cur_bri = get_bri(&config.bri.run_cmd);
+    pub fn from_config(config: &Arc<AppConf>, monitors: &[gtk4::gdk::Monitor]) -> Self {
+        let mut displays = vec![];
+        let mut cur_bri = vec![];
+
+        if let BriCmdProvider::Ddc = config.bri.run_cmd {
+            for (ind, monitor) in monitors.iter().enumerate() {
+                let (bri, display) = open_worker(ind, monitor);
+                cur_bri.push(bri);
+                displays.push(display);
+            }
+        } else {
+            cur_bri = vec![get_bri(&config.bri.run_cmd); monitors.len()];
+        }
+
         BriContext {
             cur_bri,
+            displays,
             bri_tasks_window: {
                 let mut res = vec![];
-                for _ in 0..monitor_count {
+                for _ in 0..monitors.len() {
                     res.push(HashMap::new());
                 }
                 res
             },
-            bri_tasks: HashMap::new(),
         }
     }
 
-    pub fn set_virtual_brightness(&mut self, val: f64) -> f64 {
-        if val > 100f64 {
-            self.cur_bri = 100f64;
-        } else if val < 0f64 {
-            self.cur_bri = 0f64;
+    // monitors from position on get new windows, the ones that only moved keep their brightness
+    // and ddc display, which takes a few round trips to open
+    pub fn reset_monitors(
+        &mut self,
+        config: &AppConf,
+        position: usize,
+        old_ids: &[String],
+        monitors: &[gtk4::gdk::Monitor],
+    ) {
+        let start = position.min(self.cur_bri.len());
+        for tasks in self.bri_tasks_window.drain(start..) {
+            tasks.into_values().for_each(|handle| handle.abort());
+        }
+        self.bri_tasks_window
+            .resize_with(monitors.len(), HashMap::new);
+
+        if let BriCmdProvider::Ddc = config.bri.run_cmd {
+            let mut kept = old_ids
+                .iter()
+                .skip(start)
+                .cloned()
+                .zip(
+                    self.cur_bri
+                        .drain(start..)
+                        .zip(self.displays.drain(start..)),
+                )
+                .collect::<HashMap<String, (f64, Option<DdcWorker>)>>();
+
+            for (ind, monitor) in monitors.iter().enumerate().skip(start) {
+                let (bri, display) = kept
+                    .remove(&MonitorInfo::from_monitor(monitor).id())
+                    .unwrap_or_else(|| open_worker(ind, monitor));
+                self.cur_bri.push(bri);
+                self.displays.push(display);
+            }
+        } else {
+            let bri = self
+                .cur_bri
+                .first()
+                .copied()
+                .unwrap_or_else(|| get_bri(&config.bri.run_cmd));
+            self.cur_bri.resize(monitors.len(), bri);
+        }
+    }
+
+    // monitors sharing one brightness value, every monitor with ddc and all of them otherwise
+    fn groups(&self, config: &AppConf, monitors: &[usize]) -> Vec<Vec<usize>> {
+        if let BriCmdProvider::Ddc = config.bri.run_cmd {
+            monitors
+                .iter()
+                .filter(|m| **m < self.cur_bri.len())
+                .map(|m| vec![*m])
+                .collect()
+        } else if self.cur_bri.is_empty() {
+            vec![]
         } else {
-            self.cur_bri = val;
+            vec![(0..self.cur_bri.len()).collect()]
+        }
+    }
+
+    fn get_virtual_brightness(&self, group: &[usize]) -> f64 {
+        group
+            .first()
+            .and_then(|m| self.cur_bri.get(*m))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_virtual_brightness(&mut self, group: &[usize], val: f64) -> f64 {
+        let val = val.clamp(0f64, 100f64);
+        for monitor in group {
+            self.cur_bri[*monitor] = val;
+        }
+
+        val
+    }
+
+    // the osd follows once the displays took the value, see Bri::Applied
+    fn queue_ddc(
+        &mut self,
+        targets: Vec<(Vec<usize>, f64)>,
+        sender: UnboundedSender<DaemonEvt>,
+        reply: &mut Option<UnboundedSender<DaemonRes>>,
+    ) -> Result<(), DaemonErr> {
+        let jobs = targets
+            .into_iter()
+            .flat_map(|(group, val)| group.into_iter().map(move |m| (m, val)))
+            .collect::<Vec<(usize, f64)>>();
+
+        // nothing is sent unless every monitor can take it
+        if let Some((monitor, _)) = jobs
+            .iter()
+            .find(|(m, _)| !matches!(self.displays.get(*m), Some(Some(_))))
+        {
+            return Err(DaemonErr::Backend(format!(
+                "Monitor {} has no DDC/CI",
+                monitor
+            )));
+        }
+
+        if jobs.is_empty() {
+            return Ok(());
+        }
+
+        let pending = Arc::new(Mutex::new(PendingReply {
+            left: jobs.len(),
+            error: None,
+            reply: reply.take(),
+        }));
+
+        for (monitor, val) in jobs {
+            // requests build on the value asked for, not the one the display has right now
+            let val = self.set_virtual_brightness(&[monitor], val);
+            if let Some(Some(worker)) = self.displays.get_mut(monitor) {
+                worker.queued += 1;
+                let job = DdcJob {
+                    monitor,
+                    val,
+                    sender: sender.clone(),
+                    pending: pending.clone(),
+                };
+                if let Err(mpsc::SendError(job)) = worker.jobs.send(job) {
+                    PendingReply::done(
+                        &job.pending,
+                        Err(format!("Lost the DDC/CI thread of monitor {}", monitor)),
+                    );
+                }
+            }
         }
 
-        self.cur_bri
+        Ok(())
     }
 }
 
-fn update_display_info(config: Arc<AppConf>, windows: &[Window], val: f64) {
+// a ddc/ci write takes 50ms and more, so every display waits on its own thread
+pub struct DdcWorker {
+    jobs: mpsc::Sender<DdcJob>,
+    // writes that aren't back yet, the brightness stays the one asked for until the last one is
+    queued: usize,
+}
+
+struct DdcJob {
+    monitor: usize,
+    val: f64,
+    sender: UnboundedSender<DaemonEvt>,
+    pending: Arc<Mutex<PendingReply>>,
+}
+
+// the client is answered once every display of its request is done
+struct PendingReply {
+    left: usize,
+    error: Option<String>,
+    reply: Option<UnboundedSender<DaemonRes>>,
+}
+
+impl PendingReply {
+    fn done(pending: &Mutex<PendingReply>, res: Result<(), String>) {
+        let mut pending = match pending.lock() {
+            Ok(g) => g,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if let Err(e) = res {
+            pending.error.get_or_insert(e);
+        }
+
+        pending.left -= 1;
+        if pending.left > 0 {
+            return;
+        }
+
+        let res = match pending.error.take() {
+            Some(e) => DaemonRes::Failure(DaemonErr::Backend(e).into()),
+            None => DaemonRes::Success,
+        };
+        if let Some(reply) = pending.reply.take() {
+            if let Err(e) = reply.send(res) {
+                println!("Bri: Failed to answer the client: {:?}", e);
+            }
+        }
+    }
+}
+
+impl DdcWorker {
+    fn spawn(mut display: DdcDisplay, mut cur: f64) -> Self {
+        let (jobs, receiver) = mpsc::channel::<DdcJob>();
+
+        // ends with the last sender, when the monitor goes away
+        std::thread::spawn(move || {
+            for job in receiver {
+                let from = cur;
+                let res = display.set_brightness(job.val).map_err(|e| {
+                    format!("Failed to set brightness of monitor {}: {}", job.monitor, e)
+                });
+                match &res {
+                    Ok(()) => cur = job.val,
+                    Err(e) => println!("Bri: {}", e),
+                }
+
+                job.sender
+                    .send(DaemonEvt {
+                        evt: DaemonCmdType::Bri(Bri::Applied(from, cur)),
+                        sender: None,
+                        uuid: None,
+                        monitors: vec![job.monitor],
+                    })
+                    .unwrap_or_else(|e| println!("Bri: failed to update: {}", e));
+                PendingReply::done(&job.pending, res);
+            }
+        });
+
+        DdcWorker { jobs, queued: 0 }
+    }
+}
+
+fn open_worker(ind: usize, monitor: &gtk4::gdk::Monitor) -> (f64, Option<DdcWorker>) {
+    match open_display(ind, monitor) {
+        Some(mut display) => {
+            let bri = get_ddc_bri(&mut display);
+            (bri, Some(DdcWorker::spawn(display, bri)))
+        }
+        None => (0f64, None),
+    }
+}
+
+fn open_display(ind: usize, monitor: &gtk4::gdk::Monitor) -> Option<DdcDisplay> {
+    let connector = if let Some(c) = monitor.connector() {
+        c
+    } else {
+        println!("Bri: Monitor {} has no connector name, ignoring...", ind);
+        return None;
+    };
+
+    match ddc::open_connector(&connector) {
+        Ok(display) => Some(display),
+        Err(e) => {
+            println!("Bri: No DDC/CI on monitor {} ({}): {}", ind, connector, e);
+            None
+        }
+    }
+}
+
+fn get_ddc_bri(display: &mut DdcDisplay) -> f64 {
+    display.get_brightness().unwrap_or_else(|e| {
+        println!("Bri: Failed to get brightness over DDC/CI: {}", e);
+        0f64
+    })
+}
+
+fn windows_of<'a>(windows: &'a [Window], group: &'a [usize]) -> impl Iterator<Item = &'a Window> {
+    group.iter().filter_map(|m| windows.get(*m))
+}
+
+fn update_display_info<'a>(
+    config: Arc<AppConf>,
+    windows: impl Iterator<Item = &'a Window>,
+    val: f64,
+) {
     for window in windows {
         let child = if let Some(w) = window.child() {
             w
@@ -78,24 +337,45 @@ fn update_display_info(config: Arc<AppConf>, windows: &[Window], val: f64) {
 
 fn murph(
     sender: UnboundedSender<DaemonEvt>,
-    mut current: f64,
     context: &mut BriContext,
+    group: Vec<usize>,
     target: f64,
     config: Arc<AppConf>,
-    window: &[Window],
-    monitors: Vec<usize>,
-) {
+    windows: &[Window],
+) -> Result<(), DaemonErr> {
+    let current = context.get_virtual_brightness(&group);
     // shadowing target to adjust it to an appropriate value
-    let target = context.set_virtual_brightness(target);
-    let task_map = &mut context.bri_tasks;
-    if let Some(handle) = task_map.get(&VolBriTaskType::MurphValue) {
+    let target = context.set_virtual_brightness(&group, target);
+
+    // the osd only shows what the backend took
+    if let Err(e) = set_bri(&config.bri.run_cmd, target) {
+        context.set_virtual_brightness(&group, current);
+        return Err(DaemonErr::Backend(e));
+    }
+
+    animate(sender, context, group, current, target, config, windows);
+
+    Ok(())
+}
+
+fn animate(
+    sender: UnboundedSender<DaemonEvt>,
+    context: &mut BriContext,
+    group: Vec<usize>,
+    mut current: f64,
+    target: f64,
+    config: Arc<AppConf>,
+    windows: &[Window],
+) {
+    let task_map = &mut context.bri_tasks_window[group[0]];
+    if let Some(handle) = task_map.get(&VolBriTaskTypeWindow::MurphValue) {
         handle.abort();
-        task_map.remove(&VolBriTaskType::MurphValue);
+        task_map.remove(&VolBriTaskTypeWindow::MurphValue);
     }
 
-    set_bri(&config.bri.run_cmd, target);
-    update_display_info(config.clone(), window, target);
+    update_display_info(config.clone(), windows_of(windows, &group), target);
 
+    let task_map = &mut context.bri_tasks_window[group[0]];
     let handle = tokio::spawn(async move {
         for _ in 0..50 {
             current += (target - current) * 0.1f64;
@@ -104,7 +384,7 @@ fn murph(
                     evt: DaemonCmdType::Bri(Bri::SetRough(current)),
                     sender: None,
                     uuid: None,
-                    monitors: monitors.clone(),
+                    monitors: group.clone(),
                 })
                 .unwrap_or_else(|e| println!("Bri: failed to update: {}", e));
             tokio::time::sleep(Duration::from_millis(10)).await;
@@ -115,15 +395,44 @@ fn murph(
                 evt: DaemonCmdType::Bri(Bri::SetRough(target)),
                 sender: None,
                 uuid: None,
-                monitors,
+                monitors: group,
             })
             .unwrap_or_else(|e| println!("Bri: failed to update: {}", e));
     });
 
-    task_map.insert(VolBriTaskType::MurphValue, handle);
+    task_map.insert(VolBriTaskTypeWindow::MurphValue, handle);
+}
+
+// every group is tried even if one fails, the first failure is what's reported
+fn murph_groups(
+    sender: UnboundedSender<DaemonEvt>,
+    context: &mut BriContext,
+    targets: Vec<(Vec<usize>, f64)>,
+    config: Arc<AppConf>,
+    windows: &[Window],
+    reply: &mut Option<UnboundedSender<DaemonRes>>,
+) -> Result<(), DaemonErr> {
+    if let BriCmdProvider::Ddc = config.bri.run_cmd {
+        return context.queue_ddc(targets, sender, reply);
+    }
+
+    let mut res = Ok(());
+    for (group, target) in targets {
+        let group_res = murph(
+            sender.clone(),
+            context,
+            group,
+            target,
+            config.clone(),
+            windows,
+        );
+        res = res.and(group_res);
+    }
+
+    res
 }
 
-fn set_rough(val: f64, windows: &[Window]) {
+fn set_rough<'a>(val: f64, windows: impl Iterator<Item = &'a Window>) {
     for window in windows {
         let child = if let Some(widget) = window
             .child()
@@ -166,28 +475,52 @@ pub fn handle_bri_cmd(
     context: &mut BriContext,
     config: Arc<AppConf>,
     monitors: Vec<usize>,
+    reply: &mut Option<UnboundedSender<DaemonRes>>,
 ) -> Result<DaemonRes, DaemonErr> {
     match cmd {
         Bri::SetRough(val) => {
-            set_rough(val, windows);
+            for group in context.groups(&config, &monitors) {
+                set_rough(val, windows_of(windows, &group));
+            }
         }
         Bri::Set(val) => {
-            let current = context.cur_bri;
             let target = utils::round_down(val);
-            murph(sender, current, context, target, config, windows, monitors);
+            let targets = context
+                .groups(&config, &monitors)
+                .into_iter()
+                .map(|group| (group, target))
+                .collect();
+            murph_groups(sender, context, targets, config, windows, reply)?;
         }
         Bri::Get => {
-            return Ok(DaemonRes::GetBri(context.cur_bri));
+            let groups = context.groups(&config, &monitors);
+            return Ok(DaemonRes::GetBri(
+                groups
+                    .first()
+                    .map_or(0f64, |group| context.get_virtual_brightness(group)),
+            ));
         }
         Bri::Inc(val) => {
-            let current = context.cur_bri;
-            let target = utils::round_down(current + val);
-            murph(sender, current, context, target, config, windows, monitors);
+            let targets = context
+                .groups(&config, &monitors)
+                .into_iter()
+                .map(|group| {
+                    let target = utils::round_down(context.get_virtual_brightness(&group) + val);
+                    (group, target)
+                })
+                .collect();
+            murph_groups(sender, context, targets, config, windows, reply)?;
         }
         Bri::Dec(val) => {
-            let current = context.cur_bri;
-            let target = utils::round_down(current - val);
-            murph(sender, current, context, target, config, windows, monitors);
+            let targets = context
+                .groups(&config, &monitors)
+                .into_iter()
+                .map(|group| {
+                    let target = utils::round_down(context.get_virtual_brightness(&group) - val);
+                    (group, target)
+                })
+                .collect();
+            murph_groups(sender, context, targets, config, windows, reply)?;
         }
         Bri::Close => {
             for monitor in monitors {
@@ -199,6 +532,59 @@ pub fn handle_bri_cmd(
                 windows[monitor].set_visible(true);
             }
         }
+        Bri::Sync(val) => {
+            let groups = context.groups(&config, &monitors);
+            // our own changes come back through the watcher as well
+            if groups
+                .iter()
+                .all(|group| (val - context.get_virtual_brightness(group)).abs() < 0.5)
+            {
+                return Ok(DaemonRes::Success);
+            }
+
+            for group in groups {
+                if let Some(handle) =
+                    context.bri_tasks_window[group[0]].remove(&VolBriTaskTypeWindow::MurphValue)
+                {
+                    handle.abort();
+                }
+
+                let val = context.set_virtual_brightness(&group, val);
+                set_rough(val, windows_of(windows, &group));
+                update_display_info(config.clone(), windows_of(windows, &group), val);
+            }
+
+            if config.bri.watch_popup > 0f64 {
+                return handle_bri_cmd(
+                    Bri::OpenTimed(config.bri.watch_popup),
+                    windows,
+                    sender,
+                    context,
+                    config,
+                    monitors,
+                    reply,
+                );
+            }
+        }
+        Bri::Applied(from, to) => {
+            for group in context.groups(&config, &monitors) {
+                if let Some(Some(worker)) = context.displays.get_mut(group[0]) {
+                    worker.queued = worker.queued.saturating_sub(1);
+                    if worker.queued == 0 {
+                        context.set_virtual_brightness(&group, to);
+                    }
+                }
+                animate(
+                    sender.clone(),
+                    context,
+                    group,
+                    from,
+                    to,
+                    config.clone(),
+                    windows,
+                );
+            }
+        }
         Bri::OpenTimed(time) => {
             for monitor in monitors.iter() {
                 windows[*monitor].set_visible(true);
@@ -286,28 +672,40 @@ fn get_bri(cmd: &BriCmdProvider) -> f64 {
 
             (cur * 100 / max) as f64
         }
-        BriCmdProvider::NoCmd => 0f64,
+        BriCmdProvider::Custom(cmd) => utils::run_shell(&cmd.get)
+            .ok()
+            .and_then(|out| utils::parse_level(&out, &cmd.regex))
+            .map(|(val, _)| val)
+            .unwrap_or_else(|| {
+                println!("Bri: Failed to get brightness from `{}`", cmd.get);
+                0f64
+            }),
+        // read per monitor through BriContext
+        BriCmdProvider::Ddc | BriCmdProvider::NoCmd => 0f64,
     }
 }
 
-fn set_bri(cmd: &BriCmdProvider, val: f64) {
+fn set_bri(cmd: &BriCmdProvider, val: f64) -> Result<(), String> {
     match cmd {
         BriCmdProvider::Builtin => {
             let brightness = backlight::Brightness::default();
-            brightness.set_percent(val as i32).unwrap_or_else(|e| {
-                println!("Error trying to set the current brightness: {}", e);
-                false
-            });
+            brightness
+                .set_percent(val as i32)
+                .map(|_| ())
+                .map_err(|e| format!("Failed to set the brightness: {}", e))
         }
 
-        BriCmdProvider::BrightnessCtl => {
-            let _ = Command::new("/bin/sh")
-                .arg("-c")
-                .arg(format!("brightnessctl set {}%", val))
-                .output();
+        BriCmdProvider::BrightnessCtl => utils::run_shell(&format!("brightnessctl set {}%", val))
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+
+        BriCmdProvider::Custom(cmd) => {
+            utils::run_shell(&cmd.set.replace("{value}", &val.to_string()))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
         }
 
-        BriCmdProvider::NoCmd => {}
+        BriCmdProvider::Ddc | BriCmdProvider::NoCmd => Ok(()),
     }
 }
 
@@ -332,11 +730,23 @@ enum IconRefHolder<'a> {
     Text(&'a Label),
 }
 
+// the parts of the osd with a class of their own, after bri-
+const CSS_PARTS: [&str; 5] = ["window", "box", "icon", "label", "scale"];
+
+fn css_class(part: &str) -> String {
+    format!("bri-{}", part)
+}
+
+pub fn css_classes() -> Vec<String> {
+    CSS_PARTS.iter().map(|part| css_class(part)).collect()
+}
+
 pub fn create_bri_osd(
     backend: DisplayBackend,
     app: &Application,
     config: Arc<AppConf>,
     monitor: &gtk4::gdk::Monitor,
+    cur_bri: f64,
 ) -> ApplicationWindow {
     let result = window::create_window(
         &backend,
@@ -345,21 +755,19 @@ pub fn create_bri_osd(
         gtk4_layer_shell::KeyboardMode::None,
         monitor,
     );
-    result.add_css_class("bri-window");
-
-    let cur_bri = get_bri(&config.bri.run_cmd);
+    result.add_css_class(&css_class("window"));
 
     let adjustment = Adjustment::new(cur_bri, 0.0, 100f64, 0.1, 0.0, 0.0);
 
     let wrapper: Box = Box::new(gtk4::Orientation::Horizontal, 10);
     wrapper.set_halign(gtk4::Align::Center);
-    wrapper.add_css_class("bri-box");
+    wrapper.add_css_class(&css_class("box"));
 
     let text_icon = Label::new(Some(""));
-    text_icon.add_css_class("bri-icon");
+    text_icon.add_css_class(&css_class("icon"));
 
     let svg_icon = Image::new();
-    svg_icon.add_css_class("bri-icon");
+    svg_icon.add_css_class(&css_class("icon"));
 
     if config.bri.use_svg {
         set_icon(config.clone(), IconRefHolder::Svg(&svg_icon), cur_bri);
@@ -368,7 +776,7 @@ pub fn create_bri_osd(
     }
 
     let label = Label::new(Some(&(cur_bri as i64).to_string()));
-    label.add_css_class("bri-label");
+    label.add_css_class(&css_class("label"));
 
     if config.vol.use_svg {
         wrapper.append(&svg_icon);
@@ -378,7 +786,7 @@ pub fn create_bri_osd(
 
     let scale = Scale::new(gtk4::Orientation::Horizontal, Some(&adjustment));
     scale.set_width_request(100);
-    scale.add_css_class("bri-scale");
+    scale.add_css_class(&css_class("scale"));
     scale.set_sensitive(false);
     wrapper.append(&scale);
     wrapper.append(&label);
@@ -1,22 +1,35 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_inline_default::serde_inline_default;
 use smart_default::SmartDefault;
 
-use super::window::WindowDescriptor;
+use super::config_check::{check_sources, ConfigIssue};
+use super::config_source;
+use super::window::{KeyboardModeWrapper, WindowDescriptor};
+use crate::daemon::notification::denote::Urgency;
+use crate::daemon::structs::DaemonCmdType;
 
 pub const DEFAULT_CSS_PATH: &str = "/usr/share/dvvidget/style.css";
 pub const DEFAULT_VOL_CMD: VolCmdProvider = VolCmdProvider::Wpctl;
 pub const DEFAULT_BRI_CMD: BriCmdProvider = BriCmdProvider::BrightnessCtl;
 
-#[derive(Clone, Deserialize, Debug, SmartDefault)]
+#[derive(Clone, Serialize, Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields)]
 #[serde_inline_default]
 pub struct AppConf {
     #[serde(default = "AppConfGeneral::default")]
     pub general: AppConfGeneral,
     #[serde(default = "AppConfVol::default")]
     pub vol: AppConfVol,
+    // the microphone osd shares everything with the volume one except the defaults
+    #[serde(default = "AppConfVol::mic", deserialize_with = "deserialize_mic")]
+    #[default(_code = "AppConfVol::mic()")]
+    pub mic: AppConfVol,
     #[serde(default = "AppConfBri::default")]
     pub bri: AppConfBri,
     #[serde(default = "AppConfDvoty::default")]
@@ -26,44 +39,332 @@ pub struct AppConf {
 }
 
 #[serde_inline_default]
-#[derive(Clone, Deserialize, Debug, SmartDefault)]
+#[derive(Clone, Serialize, Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfDenote {
     #[serde_inline_default(true)]
     #[default = true]
     pub enable: bool,
+
+    #[serde_inline_default(WindowDescriptor {anchor_top: true, anchor_right: true, margin_top: 10, margin_right: 10, visible_on_start: false, ..Default::default()})]
+    #[default(
+        _code = "WindowDescriptor {anchor_top: true, anchor_right: true, margin_top: 10, margin_right: 10, visible_on_start: false, ..Default::default()}"
+    )]
+    pub window: WindowDescriptor,
+
+    #[serde_inline_default(400)]
+    #[default = 400]
+    pub width: i32,
+
+    #[serde_inline_default(10)]
+    #[default = 10]
+    pub spacing: u32,
+
+    // seconds, used when the client leaves the expiration to the server
+    // 0 keeps the notifications on screen until they are clicked
+    #[serde_inline_default(5f64)]
+    #[default(_code = "5f64")]
+    pub timeout: f64,
+
+    // same as timeout but for critical notifications
+    #[serde_inline_default(0f64)]
+    #[default(_code = "0f64")]
+    pub critical_timeout: f64,
+
+    #[serde_inline_default(5)]
+    #[default = 5]
+    pub max_notifications: usize,
+
+    #[serde_inline_default(WindowDescriptor {anchor_top: true, anchor_right: true, margin_top: 10, margin_right: 10, visible_on_start: false, ..Default::default()})]
+    #[default(
+        _code = "WindowDescriptor {anchor_top: true, anchor_right: true, margin_top: 10, margin_right: 10, visible_on_start: false, ..Default::default()}"
+    )]
+    pub center_window: WindowDescriptor,
+
+    // where the notification center opens, popups show up on every monitor
+    #[serde(default)]
+    pub show_on: ShowOn,
+
+    #[serde_inline_default(600)]
+    #[default = 600]
+    pub center_height: i32,
+
+    // keep past notifications in the notification center
+    #[serde_inline_default(true)]
+    #[default = true]
+    pub history: bool,
+
+    #[serde_inline_default(100)]
+    #[default = 100]
+    pub history_length: usize,
+
+    // do not disturb on start
+    #[serde_inline_default(false)]
+    #[default = false]
+    pub dnd: bool,
+
+    #[serde_inline_default(vec![])]
+    #[default(_code = "vec![]")]
+    pub rules: Vec<DenoteRule>,
+
+    // loaded on top of general.css_path, for the popups and the notification center
+    #[serde(default)]
+    pub css_path: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, SmartDefault, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DenoteAction {
+    // popup even when do not disturb is on
+    #[default]
+    Show,
+    // hide while do not disturb is on, critical notifications included
+    Silence,
+    // never popup, only keep it in the history
+    Suppress,
+}
+
+// every given field has to match, the first matching rule wins
+#[derive(Clone, Serialize, Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields)]
+pub struct DenoteRule {
+    #[serde(default)]
+    pub app_name: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_optional_regex",
+        serialize_with = "serialize_optional_regex"
+    )]
+    pub summary: Option<Regex>,
+    #[serde(default)]
+    pub urgency: Option<Urgency>,
+    pub action: DenoteAction,
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Regex::new(&s).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_regex(deserializer).map(Some)
+}
+
+fn serialize_regex<S>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(regex.as_str())
+}
+
+fn serialize_optional_regex<S>(regex: &Option<Regex>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match regex {
+        Some(r) => serializer.serialize_some(r.as_str()),
+        None => serializer.serialize_none(),
+    }
 }
 
 #[serde_inline_default]
 #[derive(Clone, Serialize, Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfGeneral {
     #[serde_inline_default("/usr/share/dvvidget/style.css".to_string())]
     #[default = "/usr/share/dvvidget/style.css"]
     pub css_path: String,
+    // stylesheets for the windows on one monitor, by connector name or model
+    #[serde(default)]
+    pub monitor_css: BTreeMap<String, String>,
 }
 
 #[derive(Clone, Debug, SmartDefault)]
 pub enum VolCmdProvider {
     #[default]
     Wpctl,
+    // talks to pulseaudio or pipewire-pulse over its socket
+    Pulse,
+    Custom(CustomVolCmd),
     NoCmd,
 }
 
+// shell commands, {value} gets replaced with the new value
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomVolCmd {
+    pub get: String,
+    pub set: String,
+    // {value} is 1 to mute, 0 to unmute
+    #[serde(default)]
+    pub mute: Option<String>,
+    #[serde(default)]
+    pub toggle_mute: Option<String>,
+    // parses the output of get, see CUSTOM_CMD_REGEX
+    #[serde(
+        default = "default_custom_regex",
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub regex: Regex,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomBriCmd {
+    pub get: String,
+    pub set: String,
+    #[serde(
+        default = "default_custom_regex",
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub regex: Regex,
+}
+
+// the value group is required, max turns the value into a percentage, and the volume counts
+// as muted if the muted group matches anything
+pub const CUSTOM_CMD_REGEX: &str = r"(?P<value>\d+(?:\.\d+)?)";
+
+fn default_custom_regex() -> Regex {
+    Regex::new(CUSTOM_CMD_REGEX).unwrap()
+}
+
+// either a provider name or a table of custom commands
+struct ProviderVisitor<T, C> {
+    names: &'static str,
+    from_name: fn(&str) -> Option<T>,
+    from_custom: fn(C) -> T,
+}
+
+impl<'de, T, C: Deserialize<'de>> serde::de::Visitor<'de> for ProviderVisitor<T, C> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            formatter,
+            "one of {} or a table of custom commands",
+            self.names
+        )
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        (self.from_name)(&v.to_lowercase()).ok_or_else(|| {
+            E::custom(format!(
+                "unknown provider \"{}\", expected one of {} or a table of custom commands",
+                v, self.names
+            ))
+        })
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        C::deserialize(serde::de::value::MapAccessDeserializer::new(map)).map(self.from_custom)
+    }
+}
+
 impl<'de> Deserialize<'de> for VolCmdProvider {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        Ok(match s.as_str() {
-            "Wpctl" | "wpctl" => DEFAULT_VOL_CMD,
-            "none" | "None" => VolCmdProvider::NoCmd,
-            _ => DEFAULT_VOL_CMD,
+        deserializer.deserialize_any(ProviderVisitor {
+            names: "wpctl, pulse, none",
+            from_name: |name| match name {
+                "wpctl" => Some(VolCmdProvider::Wpctl),
+                "pulse" | "pipewire" => Some(VolCmdProvider::Pulse),
+                "none" => Some(VolCmdProvider::NoCmd),
+                _ => None,
+            },
+            from_custom: VolCmdProvider::Custom,
         })
     }
 }
 
+impl Serialize for VolCmdProvider {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            VolCmdProvider::Wpctl => serializer.serialize_str("wpctl"),
+            VolCmdProvider::Pulse => serializer.serialize_str("pulse"),
+            VolCmdProvider::Custom(cmd) => cmd.serialize(serializer),
+            VolCmdProvider::NoCmd => serializer.serialize_str("none"),
+        }
+    }
+}
+
+// where a widget shows up when the client doesn't pick a monitor
+#[derive(Clone, Debug, SmartDefault, PartialEq, Eq)]
+pub enum ShowOn {
+    #[default]
+    All,
+    Focused,
+    // connector names or models, every monitor if none of them is connected
+    Monitors(Vec<String>),
+}
+
+struct ShowOnVisitor;
+
+impl<'de> serde::de::Visitor<'de> for ShowOnVisitor {
+    type Value = ShowOn;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "\"all\", \"focused\" or a list of monitor names")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        match v.to_lowercase().as_str() {
+            "all" => Ok(ShowOn::All),
+            "focused" => Ok(ShowOn::Focused),
+            _ => Err(E::custom(format!(
+                "unknown show_on \"{}\", expected \"all\", \"focused\" or a list of monitor names",
+                v
+            ))),
+        }
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut names = vec![];
+        while let Some(name) = seq.next_element::<String>()? {
+            names.push(name);
+        }
+
+        Ok(ShowOn::Monitors(names))
+    }
+}
+
+impl<'de> Deserialize<'de> for ShowOn {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ShowOnVisitor)
+    }
+}
+
+impl Serialize for ShowOn {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            ShowOn::All => serializer.serialize_str("all"),
+            ShowOn::Focused => serializer.serialize_str("focused"),
+            ShowOn::Monitors(names) => names.serialize(serializer),
+        }
+    }
+}
+
 #[serde_inline_default]
-#[derive(Clone, Debug, Deserialize, SmartDefault)]
+#[derive(Clone, Debug, Serialize, Deserialize, SmartDefault)]
+#[serde(deny_unknown_fields)]
 pub struct IconDescriptor {
     #[serde_inline_default(0.0f64)]
     pub lower: f64,
@@ -84,7 +385,8 @@ impl IconDescriptor {
 }
 
 #[serde_inline_default]
-#[derive(Clone, Deserialize, Debug, SmartDefault)]
+#[derive(Clone, Serialize, Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfVol {
     #[serde_inline_default(true)]
     #[default = true]
@@ -94,11 +396,24 @@ pub struct AppConfVol {
         _code = "WindowDescriptor {anchor_bottom: true, margin_bottom: 130, ..Default::default()}"
     )]
     pub window: WindowDescriptor,
+    #[serde(default)]
+    pub show_on: ShowOn,
     #[serde_inline_default(100f64)]
     #[default(_code = "100f64")]
     pub max_vol: f64,
     #[serde_inline_default(DEFAULT_VOL_CMD)]
     pub run_cmd: VolCmdProvider,
+    // follow volume changes made by other programs
+    #[serde_inline_default(true)]
+    #[default = true]
+    pub watch: bool,
+    // seconds to show the osd for on outside changes, 0 to not show it
+    #[serde_inline_default(0f64)]
+    #[default(_code = "0f64")]
+    pub watch_popup: f64,
+    // sink name for [vol], source name for [mic], the default one if not given
+    #[serde(default)]
+    pub device: Option<String>,
     #[serde_inline_default(false)]
     #[default = false]
     pub use_svg: bool,
@@ -120,6 +435,154 @@ pub struct AppConfVol {
     #[serde_inline_default(" ".into())]
     #[default = " "]
     pub mute_icon: String,
+    // loaded on top of general.css_path
+    #[serde(default)]
+    pub css_path: Option<String>,
+}
+
+impl AppConfVol {
+    // the microphone osd is off by default and sits above the volume one
+    pub fn mic() -> Self {
+        AppConfVol {
+            enable: false,
+            window: WindowDescriptor {
+                anchor_bottom: true,
+                margin_bottom: 190,
+                ..Default::default()
+            },
+            icons: vec![IconDescriptor::from_val(0f64, 101f64, " ")],
+            ..Default::default()
+        }
+    }
+}
+
+// hands the keys of a table through to what's deserialized from it, noting each one down
+struct NotedKeys<'a, A> {
+    map: A,
+    seen: &'a mut Vec<String>,
+}
+
+impl<'de, A: serde::de::MapAccess<'de>> serde::de::MapAccess<'de> for NotedKeys<'_, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: serde::de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        self.map.next_key_seed(NotedKey {
+            inner: seed,
+            seen: self.seen,
+        })
+    }
+
+    fn next_value_seed<V: serde::de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.map.next_value_seed(seed)
+    }
+}
+
+// wraps the seed, the deserializer and the visitor of a key in turn, the key itself still comes
+// from the table's deserializer so its errors keep pointing at the key
+struct NotedKey<'a, T> {
+    inner: T,
+    seen: &'a mut Vec<String>,
+}
+
+impl<'de, K: serde::de::DeserializeSeed<'de>> serde::de::DeserializeSeed<'de> for NotedKey<'_, K> {
+    type Value = K::Value;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<K::Value, D::Error> {
+        self.inner.deserialize(NotedKey {
+            inner: deserializer,
+            seen: self.seen,
+        })
+    }
+}
+
+impl<'de, D: serde::Deserializer<'de>> serde::Deserializer<'de> for NotedKey<'_, D> {
+    type Error = D::Error;
+
+    fn deserialize_any<V: serde::de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_any(NotedKey {
+            inner: visitor,
+            seen: self.seen,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+impl<'de, V: serde::de::Visitor<'de>> serde::de::Visitor<'de> for NotedKey<'_, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<V::Value, E> {
+        self.seen.push(v.to_string());
+        self.inner.visit_str(v)
+    }
+
+    fn visit_borrowed_str<E: serde::de::Error>(self, v: &'de str) -> Result<V::Value, E> {
+        self.seen.push(v.to_string());
+        self.inner.visit_borrowed_str(v)
+    }
+
+    fn visit_string<E: serde::de::Error>(self, v: String) -> Result<V::Value, E> {
+        self.seen.push(v.clone());
+        self.inner.visit_string(v)
+    }
+}
+
+struct MicVisitor;
+
+impl<'de> serde::de::Visitor<'de> for MicVisitor {
+    type Value = AppConfVol;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a table")
+    }
+
+    // [mic] is read like [vol], the keys it leaves out then get the microphone defaults
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<AppConfVol, A::Error> {
+        let mut seen = vec![];
+        let mut conf =
+            AppConfVol::deserialize(serde::de::value::MapAccessDeserializer::new(NotedKeys {
+                map,
+                seen: &mut seen,
+            }))?;
+
+        let left_out = |key: &str| !seen.iter().any(|k| k == key);
+        let mic = AppConfVol::mic();
+        if left_out("enable") {
+            conf.enable = mic.enable;
+        }
+        if left_out("window") {
+            conf.window = mic.window;
+        }
+        if left_out("icons") {
+            conf.icons = mic.icons;
+        }
+
+        Ok(conf)
+    }
+}
+
+fn deserialize_mic<'de, D>(deserializer: D) -> Result<AppConfVol, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserializer.deserialize_map(MicVisitor)
 }
 
 #[derive(Clone, Debug, SmartDefault)]
@@ -127,6 +590,9 @@ pub enum BriCmdProvider {
     Builtin,
     #[default]
     BrightnessCtl,
+    // external monitors over ddc/ci, each monitor on its own
+    Ddc,
+    Custom(CustomBriCmd),
     NoCmd,
 }
 
@@ -135,18 +601,38 @@ impl<'de> Deserialize<'de> for BriCmdProvider {
     where
         D: serde::Deserializer<'de>,
     {
-        let s = String::deserialize(deserializer)?;
-        Ok(match s.to_lowercase().as_str() {
-            "builtin" => BriCmdProvider::Builtin,
-            "brightnessctl" => Self::BrightnessCtl,
-            "none" => BriCmdProvider::NoCmd,
-            _ => DEFAULT_BRI_CMD,
+        deserializer.deserialize_any(ProviderVisitor {
+            names: "builtin, brightnessctl, ddc, none",
+            from_name: |name| match name {
+                "builtin" => Some(BriCmdProvider::Builtin),
+                "brightnessctl" => Some(BriCmdProvider::BrightnessCtl),
+                "ddc" => Some(BriCmdProvider::Ddc),
+                "none" => Some(BriCmdProvider::NoCmd),
+                _ => None,
+            },
+            from_custom: BriCmdProvider::Custom,
         })
     }
 }
 
+impl Serialize for BriCmdProvider {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            BriCmdProvider::Builtin => serializer.serialize_str("builtin"),
+            BriCmdProvider::BrightnessCtl => serializer.serialize_str("brightnessctl"),
+            BriCmdProvider::Ddc => serializer.serialize_str("ddc"),
+            BriCmdProvider::Custom(cmd) => cmd.serialize(serializer),
+            BriCmdProvider::NoCmd => serializer.serialize_str("none"),
+        }
+    }
+}
+
 #[serde_inline_default]
-#[derive(Clone, Deserialize, Debug, SmartDefault)]
+#[derive(Clone, Serialize, Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfBri {
     #[serde_inline_default(true)]
     #[default = true]
@@ -156,8 +642,18 @@ pub struct AppConfBri {
         _code = "WindowDescriptor {anchor_bottom: true, margin_bottom: 130, ..Default::default()}"
     )]
     pub window: WindowDescriptor,
+    #[serde(default)]
+    pub show_on: ShowOn,
     #[serde_inline_default(DEFAULT_BRI_CMD)]
     pub run_cmd: BriCmdProvider,
+    // follow brightness changes made by other programs
+    #[serde_inline_default(true)]
+    #[default = true]
+    pub watch: bool,
+    // seconds to show the osd for on outside changes, 0 to not show it
+    #[serde_inline_default(0f64)]
+    #[default(_code = "0f64")]
+    pub watch_popup: f64,
     #[serde_inline_default(false)]
     #[default = false]
     pub use_svg: bool,
@@ -172,6 +668,9 @@ pub struct AppConfBri {
         IconDescriptor::from_val(60f64, 100f64, \"2\"),
     ]")]
     pub icons: Vec<IconDescriptor>,
+    // loaded on top of general.css_path
+    #[serde(default)]
+    pub css_path: Option<String>,
 }
 
 #[derive(Clone, SmartDefault, Debug)]
@@ -189,12 +688,31 @@ impl<'de> Deserialize<'de> for SearchEngine {
         D: serde::Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Ok(Self::from_string(&s))
+        Self::from_string(&s).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "unknown search engine \"{}\", expected google, duckduckgo, bing or [lang_]wiki",
+                s
+            ))
+        })
+    }
+}
+
+impl Serialize for SearchEngine {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            SearchEngine::Google => serializer.serialize_str("google"),
+            SearchEngine::Duckduckgo => serializer.serialize_str("duckduckgo"),
+            SearchEngine::Bing => serializer.serialize_str("bing"),
+            SearchEngine::Wikipedia(lang) => serializer.serialize_str(&format!("{}_wiki", lang)),
+        }
     }
 }
 
 impl SearchEngine {
-    pub fn from_string(input: &str) -> Self {
+    pub fn from_string(input: &str) -> Option<Self> {
         // check for Wikipedia
         let input = input.trim();
         if input.ends_with("wiki")
@@ -204,23 +722,24 @@ impl SearchEngine {
             || input.ends_with("Wikipedia")
         {
             if let Some(index) = input.find("_") {
-                return SearchEngine::Wikipedia(input[0..index].to_string());
+                return Some(SearchEngine::Wikipedia(input[0..index].to_string()));
             } else {
-                return SearchEngine::Wikipedia("en".to_string());
+                return Some(SearchEngine::Wikipedia("en".to_string()));
             }
         }
 
         match input {
-            "Goog" | "google" | "Google" | "goog" => SearchEngine::Google,
-            "DDG" | "ddg" | "Ddg" | "Duckduckgo" | "duckduckgo" => SearchEngine::Duckduckgo,
-            "bing" | "Bing" => SearchEngine::Bing,
-            _ => SearchEngine::Google,
+            "Goog" | "google" | "Google" | "goog" => Some(SearchEngine::Google),
+            "DDG" | "ddg" | "Ddg" | "Duckduckgo" | "duckduckgo" => Some(SearchEngine::Duckduckgo),
+            "bing" | "Bing" => Some(SearchEngine::Bing),
+            _ => None,
         }
     }
 }
 
 #[serde_inline_default]
-#[derive(Clone, Deserialize, Debug, SmartDefault)]
+#[derive(Clone, Serialize, Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfDvoty {
     #[serde_inline_default(true)]
     #[default = true]
@@ -230,6 +749,9 @@ pub struct AppConfDvoty {
     #[default(_code = "WindowDescriptor::default()")]
     pub window: WindowDescriptor,
 
+    #[serde(default)]
+    pub show_on: ShowOn,
+
     #[serde_inline_default(300)]
     #[default = 300]
     pub max_height: u32,
@@ -304,10 +826,15 @@ pub struct AppConfDvoty {
     #[serde_inline_default(500)]
     #[default(500)]
     pub hist_length: usize,
+
+    // loaded on top of general.css_path
+    #[serde(default)]
+    pub css_path: Option<String>,
 }
 
 #[serde_inline_default]
-#[derive(Deserialize, SmartDefault, Debug, Clone)]
+#[derive(Serialize, Deserialize, SmartDefault, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct DvotyGeneralOptions {
     #[serde_inline_default(true)]
     pub math: bool,
@@ -332,6 +859,19 @@ fn default_firefox_path() -> String {
     path.to_str().unwrap().to_owned()
 }
 
+impl AppConf {
+    pub fn show_on(&self, cmd: &DaemonCmdType) -> ShowOn {
+        match cmd {
+            DaemonCmdType::Vol(_) | DaemonCmdType::VolDevice(..) => self.vol.show_on.clone(),
+            DaemonCmdType::Mic(_) => self.mic.show_on.clone(),
+            DaemonCmdType::Bri(_) => self.bri.show_on.clone(),
+            DaemonCmdType::Dvoty(_) => self.dvoty.show_on.clone(),
+            DaemonCmdType::Notif(_) => self.denote.show_on.clone(),
+            _ => ShowOn::All,
+        }
+    }
+}
+
 pub fn default_config_path() -> PathBuf {
     if let Ok(val) = std::env::var("XDG_CONFIG_HOME") {
         let mut path = PathBuf::from(&val);
@@ -356,22 +896,84 @@ pub fn default_config_path() -> PathBuf {
     }
 }
 
-pub fn read_config(target_path: &PathBuf) -> AppConf {
-    match std::fs::read_to_string(target_path) {
-        Ok(val) => {
-            println!("there is a config");
-            toml::from_str(&val).unwrap_or_else(|e| {
-                println!("Cannot parse the config:\n{}\nGo with default", e);
-                AppConf::default()
-            })
+lazy_static! {
+    // what the server side goes by, swapped whenever the config file changes
+    static ref CURRENT_CONFIG: RwLock<Arc<AppConf>> = RwLock::new(Arc::new(AppConf::default()));
+}
+
+pub fn current_config() -> Arc<AppConf> {
+    match CURRENT_CONFIG.read() {
+        Ok(g) => g.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+pub fn set_config(config: Arc<AppConf>) {
+    match CURRENT_CONFIG.write() {
+        Ok(mut g) => *g = config,
+        Err(poisoned) => *poisoned.into_inner() = config,
+    }
+}
+
+// the config with every bad key left at its default, along with what was wrong
+// includes, config.d and the host sections are merged in before anything is deserialized
+pub fn check_config(target_path: &Path) -> Result<(AppConf, Vec<ConfigIssue>), String> {
+    config_source::collect(target_path).map(check_sources)
+}
+
+// every file the config is made of, and config.d for drop-ins to come
+pub fn config_files(target_path: &Path) -> Vec<PathBuf> {
+    match config_source::collect(target_path) {
+        Ok(sources) => sources.files,
+        Err(_) => vec![
+            target_path.to_path_buf(),
+            config_source::drop_in_dir(target_path),
+        ],
+    }
+}
+
+fn parse_config(target_path: &Path) -> Result<AppConf, String> {
+    let (config, issues) = check_config(target_path)?;
+
+    if issues.is_empty() {
+        Ok(config)
+    } else {
+        Err(issues.iter().fold(
+            format!("Config: {} has errors:", target_path.display()),
+            |acc, issue| format!("{}\n{}", acc, issue),
+        ))
+    }
+}
+
+// a typo only costs the keys it is in, the rest of the config still applies
+pub fn read_config(target_path: &Path) -> AppConf {
+    match check_config(target_path) {
+        Ok((config, issues)) => {
+            for issue in issues {
+                println!("Config: {}", issue);
+            }
+            config
         }
         Err(e) => {
-            println!(
-                "Failed to get the config from path: {:?}\nErr: {:?}, go with default",
-                target_path, e
-            );
-
+            println!("{}\nGo with default", e);
             AppConf::default()
         }
     }
 }
+
+// dvoty takes keyboard input, which can't be set from the config
+fn finish_config(mut config: AppConf) -> AppConf {
+    config.dvoty.window.keyboard_mode = KeyboardModeWrapper {
+        inner: gtk4_layer_shell::KeyboardMode::OnDemand,
+    };
+    config
+}
+
+pub fn load_config(target_path: &Path) -> AppConf {
+    finish_config(read_config(target_path))
+}
+
+// unlike on start, a broken config leaves the running one alone
+pub fn reload_config(target_path: &Path) -> Result<AppConf, String> {
+    parse_config(target_path).map(finish_config)
+}
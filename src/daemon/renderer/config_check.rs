@@ -0,0 +1,392 @@
+use std::fmt::Display;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use toml_edit::{DocumentMut, ImDocument, Item, Value};
+
+use super::config::{AppConf, IconDescriptor};
+use super::config_source::{self, ConfigLayer, ConfigSources};
+
+// one step into the document
+#[derive(Clone, Debug, PartialEq)]
+enum Seg {
+    Key(String),
+    Index(usize),
+}
+
+fn path_to_string(path: &[Seg]) -> String {
+    let mut res = String::new();
+
+    for seg in path {
+        match seg {
+            Seg::Key(key) => {
+                if !res.is_empty() {
+                    res.push('.');
+                }
+                res.push_str(key);
+            }
+            Seg::Index(ind) => res.push_str(&format!("[{}]", ind)),
+        }
+    }
+
+    res
+}
+
+fn key_path(keys: &str) -> Vec<Seg> {
+    keys.split('.')
+        .map(|key| Seg::Key(key.to_string()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigIssue {
+    // None if it's about the config as a whole
+    pub file: Option<PathBuf>,
+    // 1 based, None if the issue can't be pinned to a place in the file
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    // dotted path of the key, empty for syntax errors
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn new(
+        file: Option<&Path>,
+        text: &str,
+        span: Option<Range<usize>>,
+        key: String,
+        message: String,
+    ) -> Self {
+        let position = span.map(|span| line_col(text, span.start));
+
+        ConfigIssue {
+            file: file.map(Path::to_path_buf),
+            line: position.map(|p| p.0),
+            column: position.map(|p| p.1),
+            key,
+            message: one_line(&message),
+        }
+    }
+
+    fn in_layers(layers: &[ConfigLayer], path: &[Seg], message: String) -> Self {
+        match locate_in_layers(layers, path) {
+            Some((layer, span)) => ConfigIssue::new(
+                Some(&layer.file),
+                layer.doc.raw(),
+                Some(span),
+                path_to_string(path),
+                message,
+            ),
+            None => ConfigIssue::new(None, "", None, path_to_string(path), message),
+        }
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+
+        if !self.key.is_empty() {
+            write!(f, "{}: ", self.key)?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+// toml puts the hint on a line of its own
+fn one_line(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |rest| rest.chars().count())
+        + 1;
+
+    (line, column)
+}
+
+// the children of a table or an array, keys come with their own span
+fn children(item: &Item) -> Vec<(Seg, Option<Range<usize>>, &Item)> {
+    if let Some(table) = item.as_table_like() {
+        return table
+            .iter()
+            .filter_map(|(key, _)| table.get_key_value(key))
+            .map(|(key, child)| (Seg::Key(key.get().to_string()), key.span(), child))
+            .collect();
+    }
+
+    let len = match item {
+        Item::ArrayOfTables(arr) => arr.len(),
+        Item::Value(Value::Array(arr)) => arr.len(),
+        _ => 0,
+    };
+
+    (0..len)
+        .filter_map(|ind| item.get(ind).map(|child| (Seg::Index(ind), None, child)))
+        .collect()
+}
+
+// the path of the key or value the deserializer complained about
+fn find_span(item: &Item, span: &Range<usize>, path: &mut Vec<Seg>) -> bool {
+    for (seg, key_span, child) in children(item) {
+        path.push(seg);
+
+        if key_span.as_ref() == Some(span)
+            || child.span().as_ref() == Some(span)
+            || find_span(child, span, path)
+        {
+            return true;
+        }
+
+        path.pop();
+    }
+
+    false
+}
+
+fn locate(item: &Item, path: &[Seg]) -> Option<Range<usize>> {
+    let (first, rest) = path.split_first()?;
+    let (_, key_span, child) = children(item)
+        .into_iter()
+        .find(|(seg, _, _)| seg == first)?;
+
+    if rest.is_empty() {
+        key_span.or_else(|| child.span())
+    } else {
+        locate(child, rest)
+    }
+}
+
+// the last layer setting the key is the one that counts
+fn locate_in_layers<'a>(
+    layers: &'a [ConfigLayer],
+    path: &[Seg],
+) -> Option<(&'a ConfigLayer, Range<usize>)> {
+    layers.iter().rev().find_map(|layer| {
+        let full: Vec<Seg> = layer
+            .prefix
+            .iter()
+            .map(|key| Seg::Key(key.clone()))
+            .chain(path.iter().cloned())
+            .collect();
+
+        locate(layer.doc.as_item(), &full).map(|span| (layer, span))
+    })
+}
+
+// array elements can't go without shifting the ones after them, so the whole array goes
+fn remove(doc: &mut DocumentMut, path: &[Seg]) -> bool {
+    let mut path = path.to_vec();
+    while let Some(Seg::Index(_)) = path.last() {
+        path.pop();
+    }
+
+    let Some((Seg::Key(last), parent)) = path.split_last() else {
+        return false;
+    };
+
+    let mut item = doc.as_item_mut();
+    for seg in parent {
+        let next = match seg {
+            Seg::Key(key) => item.get_mut(key.as_str()),
+            Seg::Index(ind) => item.get_mut(*ind),
+        };
+
+        item = match next {
+            Some(i) => i,
+            None => return false,
+        };
+    }
+
+    item.as_table_like_mut()
+        .and_then(|table| table.remove(last))
+        .is_some()
+}
+
+fn above_zero(res: &mut Vec<(Vec<Seg>, String)>, key: &str, val: f64) {
+    if val <= 0.0 {
+        res.push((key_path(key), format!("has to be above 0, got {}", val)));
+    }
+}
+
+fn not_negative(res: &mut Vec<(Vec<Seg>, String)>, key: &str, val: f64) {
+    if val < 0.0 {
+        res.push((key_path(key), format!("can't be negative, got {}", val)));
+    }
+}
+
+fn icon_ranges(res: &mut Vec<(Vec<Seg>, String)>, key: &str, icons: &[IconDescriptor]) {
+    for (ind, icon) in icons.iter().enumerate() {
+        if icon.lower > icon.upper {
+            let mut path = key_path(key);
+            path.push(Seg::Index(ind));
+            res.push((
+                path,
+                format!("lower ({}) is above upper ({})", icon.lower, icon.upper),
+            ));
+        }
+    }
+}
+
+// what the types alone let through
+fn check_values(config: &AppConf) -> Vec<(Vec<Seg>, String)> {
+    let mut res = vec![];
+
+    above_zero(&mut res, "vol.max_vol", config.vol.max_vol);
+    above_zero(&mut res, "mic.max_vol", config.mic.max_vol);
+    above_zero(&mut res, "denote.width", config.denote.width.into());
+    above_zero(
+        &mut res,
+        "denote.center_height",
+        config.denote.center_height.into(),
+    );
+    above_zero(
+        &mut res,
+        "denote.max_notifications",
+        config.denote.max_notifications as f64,
+    );
+    above_zero(
+        &mut res,
+        "dvoty.max_mid_width",
+        config.dvoty.max_mid_width.into(),
+    );
+
+    not_negative(&mut res, "vol.watch_popup", config.vol.watch_popup);
+    not_negative(&mut res, "mic.watch_popup", config.mic.watch_popup);
+    not_negative(&mut res, "bri.watch_popup", config.bri.watch_popup);
+    not_negative(&mut res, "denote.timeout", config.denote.timeout);
+    not_negative(
+        &mut res,
+        "denote.critical_timeout",
+        config.denote.critical_timeout,
+    );
+
+    icon_ranges(&mut res, "vol.icons", &config.vol.icons);
+    icon_ranges(&mut res, "mic.icons", &config.mic.icons);
+    icon_ranges(&mut res, "bri.icons", &config.bri.icons);
+
+    res
+}
+
+// a mistake that can't be traced back to a key can't be dropped on its own, so the config goes
+// without the files from the first one that breaks it on
+fn parse_layers(layers: &[ConfigLayer], issues: &mut Vec<ConfigIssue>) -> AppConf {
+    for end in (0..layers.len()).rev() {
+        if let Ok(config) =
+            toml_edit::de::from_str::<AppConf>(&config_source::merge(&layers[..end]).to_string())
+        {
+            issues.push(ConfigIssue::new(
+                Some(&layers[end].file),
+                "",
+                None,
+                String::new(),
+                "Left out this file and the ones merged after it, the mistake above can't be traced to a key"
+                    .to_string(),
+            ));
+            return config;
+        }
+    }
+
+    AppConf::default()
+}
+
+// goes through every problem instead of stopping at the first one, each bad key or value is
+// dropped and the rest parsed again, so the config comes back with those keys at their defaults
+pub fn check_sources(sources: ConfigSources) -> (AppConf, Vec<ConfigIssue>) {
+    let ConfigSources {
+        layers, mut issues, ..
+    } = sources;
+
+    let mut current = config_source::merge(&layers).to_string();
+
+    let config = loop {
+        let e = match toml_edit::de::from_str::<AppConf>(&current) {
+            Ok(config) => break config,
+            Err(e) => e,
+        };
+
+        let doc = match ImDocument::parse(current.clone()) {
+            Ok(doc) => doc,
+            Err(_) => {
+                issues.push(ConfigIssue::new(
+                    None,
+                    "",
+                    None,
+                    String::new(),
+                    e.message().to_string(),
+                ));
+                break parse_layers(&layers, &mut issues);
+            }
+        };
+
+        // spans in the merged text are of no use to anyone, the path leads back to the file
+        let mut path = vec![];
+        let found = e
+            .span()
+            .is_some_and(|span| find_span(doc.as_item(), &span, &mut path));
+
+        issues.push(ConfigIssue::in_layers(
+            &layers,
+            &path,
+            e.message().to_string(),
+        ));
+
+        let mut doc = doc.into_mut();
+        if !found || !remove(&mut doc, &path) {
+            break parse_layers(&layers, &mut issues);
+        }
+        current = doc.to_string();
+    };
+
+    // out of range values go the same way, so the daemon never runs with what this reports
+    let values = check_values(&config);
+    let config = match current.parse::<DocumentMut>() {
+        Ok(mut doc) if !values.is_empty() => {
+            for (path, _) in &values {
+                remove(&mut doc, path);
+            }
+            match toml_edit::de::from_str::<AppConf>(&doc.to_string()) {
+                Ok(config) => config,
+                Err(e) => {
+                    issues.push(ConfigIssue::new(
+                        None,
+                        "",
+                        None,
+                        String::new(),
+                        format!(
+                            "Kept the values out of range, the config breaks without them: {}",
+                            e.message()
+                        ),
+                    ));
+                    config
+                }
+            }
+        }
+        _ => config,
+    };
+
+    for (path, message) in values {
+        issues.push(ConfigIssue::in_layers(&layers, &path, message));
+    }
+
+    (config, issues)
+}
@@ -0,0 +1,220 @@
+use std::path::{Path, PathBuf};
+
+use toml_edit::{DocumentMut, Item, Table};
+
+use super::config::AppConf;
+use super::config_source::DROP_IN_DIR;
+use super::css::DEFAULT_CSS;
+
+pub const STYLE_FILE: &str = "style.css";
+
+fn header() -> String {
+    format!(
+        "dvvidget config, every key is at its default
+keys left out fall back to the same defaults, so this can be trimmed down to what you change
+`dvvidget config check` reports mistakes, `dvvidget config dump` prints what the daemon runs with
+
+other configs to build on, relative to this file, this one overrides them
+include = [\"~/dotfiles/dvvidget/base.toml\"]
+every *.toml in {} next to this file is merged after it, in lexical order
+sections under [host.<hostname>] only apply on that machine, like
+[host.laptop.bri]
+run_cmd = \"builtin\"",
+        DROP_IN_DIR
+    )
+}
+
+fn table_doc(name: &str) -> Option<&'static str> {
+    match name {
+        "general" => Some("settings shared by every widget"),
+        "vol" => Some(
+            "volume osd
+device = \"alsa_output.pci-0000_00_1f.3.analog-stereo\" picks the sink, see `dvvidget volume list`",
+        ),
+        "mic" => Some(
+            "microphone osd, takes the same options as [vol]
+device picks the source instead of the sink",
+        ),
+        "monitor_css" => Some(
+            "stylesheets only loaded while the monitor is connected, by connector name or model
+the windows on a monitor carry .monitor-<connector>, rules that don't name it are skipped
+\"DP-1\" = \"/absolute/path/to/dp-1.css\"",
+        ),
+        "bri" => Some("brightness osd"),
+        "dvoty" => Some("launcher, calculator and search bar"),
+        "general_options" => Some("what dvoty looks through while typing"),
+        "denote" => Some(
+            "notification daemon, turning it on or off needs a restart
+rules are checked in order, the first one that matches decides
+action is one of show, silence (hidden during do not disturb) or suppress (history only)
+[[denote.rules]]
+app_name = \"Spotify\"
+summary = \"^Now playing\"
+urgency = \"low\"
+action = \"suppress\"",
+        ),
+        "icons" => Some("the icon for each range of values, lower and upper included"),
+        "window" => Some(
+            "where the window sits, the anchors pin it to the edges of the monitor
+layer is one of background, bottom, top or overlay",
+        ),
+        "center_window" => Some("the notification center, takes the same options as the popups"),
+        _ => None,
+    }
+}
+
+fn key_doc(table: &str, key: &str) -> Option<String> {
+    let doc = match (table, key) {
+        ("general", "css_path") => {
+            "the stylesheet, the built-in one is used if the file isn't there
+every widget section takes a css_path of its own too, loaded on top of this one
+whose rules have to name a class of the widget, like .sound-window label
+`dvvidget config init --print-css-classes` lists the classes to style"
+        }
+        (_, "enable") => "whether the widget is created at all",
+        (_, "show_on") => {
+            "where it shows up when -m isn't given: \"all\", \"focused\" (Hyprland and Sway),
+or a list of connector names or models like [\"DP-1\", \"eDP-1\"], all monitors if none of them is there"
+        }
+        ("vol", "max_vol") => "the highest the osd goes, above 100 amplifies",
+        ("vol", "run_cmd") => {
+            "\"wpctl\", \"pulse\" (native, works with pipewire-pulse too), \"none\", or custom commands:
+run_cmd = { get = \"amixer get Master\", set = \"amixer set Master {value}%\", toggle_mute = \"amixer set Master toggle\", regex = '\\[(?P<value>\\d+)%\\] \\[(?:on|(?P<muted>off))\\]' }
+the regex reads the output of get with the groups value, max (optional) and muted (optional)
+max only scales what get reads, {value} in set is always a percentage"
+        }
+        ("bri", "run_cmd") => {
+            "\"builtin\", \"brightnessctl\", \"ddc\", \"none\", or custom commands like
+run_cmd = { get = \"ddcutil getvcp 10\", set = \"ddcutil setvcp 10 {value}\", regex = 'current value =\\s*(?P<value>\\d+)' }
+{value} is always a percentage, the example only works for monitors going up to 100
+\"ddc\" talks to external monitors over DDC/CI and needs the i2c-dev module and access to /dev/i2c-*"
+        }
+        (_, "watch") => "follow changes made by other programs",
+        (_, "watch_popup") => "seconds to show the osd for on outside changes, 0 to not show it",
+        (_, "use_svg") => "treat the icons as paths to svg files instead of text",
+        ("vol", "mute_icon") => "shown instead of the icons while muted",
+        ("window", "exclusive") => "reserve space for the window so nothing goes under it",
+        ("window", "visible_on_start") => "show the window as soon as the daemon starts",
+        ("window", "namespace") => "the layer shell namespace, for compositor rules",
+        ("dvoty", "max_height") => "the height of the result list in pixels",
+        ("dvoty", "spacing") => "pixels between the entries",
+        ("dvoty", "search_engine") => {
+            "\"google\", \"duckduckgo\", \"bing\" or \"<lang>_wiki\" for wikipedia in that language"
+        }
+        ("dvoty", "terminal_exec") => "the terminal commands run in",
+        ("dvoty", "firefox_path") => "where the firefox profiles are, for history and bookmarks",
+        ("dvoty", "past_search_date_limit") => "days of firefox history to search",
+        ("dvoty", "past_search_limit") => "history entries to show at most",
+        ("dvoty", "bookmark_search_limit") => "bookmarks to show at most",
+        ("dvoty", "max_mid_width") => "the widest the middle of an entry gets, in pixels",
+        ("dvoty", "highlight_color") => "the color of the matching part of an entry",
+        ("dvoty", "hist_length") => "how many past inputs are remembered",
+        ("dvoty", icon) if icon.ends_with("_icon") => {
+            return Some(format!(
+                "the icon in front of {} entries",
+                icon.trim_end_matches("_icon")
+            ));
+        }
+        ("denote", "width") => "the width of a popup in pixels",
+        ("denote", "spacing") => "pixels between the popups",
+        ("denote", "timeout") => "seconds a popup stays, 0 to keep it until it's closed",
+        ("denote", "critical_timeout") => "the same for critical notifications",
+        ("denote", "max_notifications") => "popups on screen at once, older ones make way",
+        ("denote", "center_height") => "the height of the notification center in pixels",
+        ("denote", "history") => "keep closed notifications for the notification center",
+        ("denote", "history_length") => "how many of them",
+        ("denote", "dnd") => "do not disturb on start, only critical notifications pop up",
+        ("denote", "rules") => "see the comment above [denote]",
+        _ => return None,
+    };
+
+    Some(doc.to_string())
+}
+
+fn comment(doc: &str) -> String {
+    doc.lines()
+        .map(|line| format!("# {}", line).trim_end().to_string() + "\n")
+        .collect()
+}
+
+// the docs go by the table name alone, the ones sharing a layout share them
+fn section(name: &str) -> &str {
+    match name {
+        "mic" => "vol",
+        name if name.ends_with("window") => "window",
+        name => name,
+    }
+}
+
+fn annotate(table: &mut Table, name: &str) {
+    for (mut key, item) in table.iter_mut() {
+        let key_name = key.get().to_string();
+
+        match item {
+            Item::Table(sub) => {
+                if let Some(doc) = table_doc(&key_name) {
+                    sub.decor_mut().set_prefix(format!("\n{}", comment(doc)));
+                }
+                annotate(sub, &key_name);
+            }
+            // the elements share their keys, the first one carries the comments
+            Item::ArrayOfTables(arr) => {
+                if let Some(first) = arr.iter_mut().next() {
+                    if let Some(doc) = table_doc(&key_name) {
+                        first.decor_mut().set_prefix(format!("\n{}", comment(doc)));
+                    }
+                    annotate(first, &key_name);
+                }
+            }
+            _ => {
+                if let Some(doc) = key_doc(section(name), &key_name) {
+                    key.leaf_decor_mut().set_prefix(comment(&doc));
+                }
+            }
+        }
+    }
+}
+
+// the defaults of AppConf, with what every key does next to it
+pub fn default_config_toml(css_path: &Path) -> Result<String, String> {
+    let mut config = AppConf::default();
+    config.general.css_path = css_path.to_string_lossy().to_string();
+
+    let text = toml::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize the default config: {}", e))?;
+    let mut doc = text
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("Failed to parse the default config: {}", e))?;
+
+    annotate(doc.as_table_mut(), "");
+
+    Ok(format!("{}{}", comment(&header()), doc))
+}
+
+// writes the config and a stylesheet next to it, returns where the stylesheet went
+pub fn init_config(config_path: &Path, force: bool) -> Result<PathBuf, String> {
+    let config_path = std::path::absolute(config_path)
+        .map_err(|e| format!("Failed to resolve {}: {}", config_path.display(), e))?;
+    let dir = config_path.parent().unwrap_or(Path::new("/"));
+    let css_path = dir.join(STYLE_FILE);
+
+    if !force {
+        if let Some(existing) = [&config_path, &css_path].into_iter().find(|p| p.exists()) {
+            return Err(format!(
+                "{} already exists, pass --force to overwrite it",
+                existing.display()
+            ));
+        }
+    }
+
+    let config = default_config_toml(&css_path)?;
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    std::fs::write(&css_path, DEFAULT_CSS)
+        .map_err(|e| format!("Failed to write {}: {}", css_path.display(), e))?;
+    std::fs::write(&config_path, config)
+        .map_err(|e| format!("Failed to write {}: {}", config_path.display(), e))?;
+
+    Ok(css_path)
+}
@@ -0,0 +1,250 @@
+use std::path::{Path, PathBuf};
+
+use toml_edit::{DocumentMut, ImDocument, Item, TableLike};
+
+use super::config_check::ConfigIssue;
+
+pub const DROP_IN_DIR: &str = "config.d";
+
+// one file, or the host section in it, in the order they are merged
+pub struct ConfigLayer {
+    pub file: PathBuf,
+    // keeps the spans for pointing at mistakes
+    pub doc: ImDocument<String>,
+    // where the layer starts in the file, empty for the file itself
+    pub prefix: Vec<String>,
+    table: DocumentMut,
+}
+
+#[derive(Default)]
+pub struct ConfigSources {
+    pub layers: Vec<ConfigLayer>,
+    // everything that was read or tried to, for the file watcher
+    pub files: Vec<PathBuf>,
+    pub issues: Vec<ConfigIssue>,
+}
+
+// config.d next to the config, merged after it
+pub fn drop_in_dir(config_path: &Path) -> PathBuf {
+    let mut dir = config_path.to_path_buf();
+    dir.pop();
+    dir.push(DROP_IN_DIR);
+    dir
+}
+
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+
+    let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8(buf[..end].to_vec()).ok()
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn issue_at(doc: &ImDocument<String>, file: &Path, key: &str, message: String) -> ConfigIssue {
+    let span = doc
+        .as_table()
+        .get_key_value(key)
+        .and_then(|(k, _)| k.span());
+
+    ConfigIssue::new(Some(file), doc.raw(), span, key.to_string(), message)
+}
+
+// includes come first so the file can override them, then the file, then its host section
+fn add_file(
+    sources: &mut ConfigSources,
+    file: PathBuf,
+    host: Option<&str>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    // a symlink into a dotfiles repo gets the real file watched
+    let file = file.canonicalize().unwrap_or(file);
+    sources.files.push(file.clone());
+
+    let text = std::fs::read_to_string(&file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+
+    let doc = match ImDocument::parse(text.clone()) {
+        Ok(doc) => doc,
+        Err(e) => {
+            sources.issues.push(ConfigIssue::new(
+                Some(&file),
+                &text,
+                e.span(),
+                String::new(),
+                e.message().to_string(),
+            ));
+            return Ok(());
+        }
+    };
+
+    stack.push(file.clone());
+    add_includes(sources, &doc, &file, host, stack);
+    stack.pop();
+
+    let Ok(table) = text.parse::<DocumentMut>() else {
+        return Ok(());
+    };
+
+    let host_section = match doc.as_table().get("host") {
+        None => None,
+        Some(hosts) if hosts.is_table_like() => host.filter(|name| hosts.get(name).is_some()),
+        Some(_) => {
+            sources.issues.push(issue_at(
+                &doc,
+                &file,
+                "host",
+                "has to be a table of hostnames".to_string(),
+            ));
+            None
+        }
+    };
+
+    // the host section goes on top of the file it's in
+    let host_layer = host_section.map(|name| ConfigLayer {
+        file: file.clone(),
+        doc: doc.clone(),
+        prefix: vec!["host".to_string(), name.to_string()],
+        table: table.clone(),
+    });
+
+    sources.layers.push(ConfigLayer {
+        file,
+        doc,
+        prefix: vec![],
+        table,
+    });
+    sources.layers.extend(host_layer);
+
+    Ok(())
+}
+
+fn add_includes(
+    sources: &mut ConfigSources,
+    doc: &ImDocument<String>,
+    file: &Path,
+    host: Option<&str>,
+    stack: &mut Vec<PathBuf>,
+) {
+    let Some(include) = doc.as_table().get("include") else {
+        return;
+    };
+
+    let Some(paths) = include
+        .as_array()
+        .and_then(|arr| arr.iter().map(|v| v.as_str()).collect::<Option<Vec<_>>>())
+    else {
+        sources.issues.push(issue_at(
+            doc,
+            file,
+            "include",
+            "has to be a list of paths".to_string(),
+        ));
+        return;
+    };
+
+    let dir = file.parent().unwrap_or(Path::new("."));
+
+    for path in paths {
+        // relative to the file doing the including
+        let target = dir.join(expand_home(path));
+        let target = target.canonicalize().unwrap_or(target);
+
+        if stack.contains(&target) {
+            sources.issues.push(issue_at(
+                doc,
+                file,
+                "include",
+                format!("{} includes itself", target.display()),
+            ));
+            continue;
+        }
+
+        if let Err(e) = add_file(sources, target, host, stack) {
+            sources.issues.push(issue_at(doc, file, "include", e));
+        }
+    }
+}
+
+// every file read for the config, in the order they are merged
+pub fn collect(config_path: &Path) -> Result<ConfigSources, String> {
+    let mut sources = ConfigSources::default();
+    let host = hostname();
+
+    add_file(
+        &mut sources,
+        config_path.to_path_buf(),
+        host.as_deref(),
+        &mut vec![],
+    )?;
+
+    let dir = drop_in_dir(config_path);
+    sources.files.push(dir.clone());
+
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        let mut drop_ins: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        drop_ins.sort();
+
+        for path in drop_ins {
+            if let Err(e) = add_file(&mut sources, path, host.as_deref(), &mut vec![]) {
+                sources
+                    .issues
+                    .push(ConfigIssue::new(None, "", None, String::new(), e));
+            }
+        }
+    }
+
+    Ok(sources)
+}
+
+// tables are merged key by key, anything else is replaced, arrays included
+fn merge_table(target: &mut dyn TableLike, source: &dyn TableLike) {
+    for (key, item) in source.iter() {
+        match (target.get_mut(key), item.as_table_like()) {
+            (Some(existing), Some(table)) if existing.is_table_like() => {
+                if let Some(existing) = existing.as_table_like_mut() {
+                    merge_table(existing, table);
+                }
+            }
+            _ => {
+                target.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+fn layer_root(layer: &ConfigLayer) -> Option<&dyn TableLike> {
+    let mut item: &Item = layer.table.as_item();
+    for key in &layer.prefix {
+        item = item.get(key.as_str())?;
+    }
+    item.as_table_like()
+}
+
+pub fn merge(layers: &[ConfigLayer]) -> DocumentMut {
+    let mut doc = DocumentMut::new();
+
+    for layer in layers {
+        if let Some(root) = layer_root(layer) {
+            merge_table(doc.as_table_mut(), root);
+        }
+    }
+
+    // only there to put the config together
+    doc.remove("include");
+    doc.remove("host");
+
+    doc
+}
@@ -0,0 +1,413 @@
+use std::path::{Path, PathBuf};
+
+use gtk4::prelude::FileExt;
+use gtk4::{gdk, CssProvider};
+use notify::{Event, RecommendedWatcher, Watcher};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::config::AppConf;
+use super::dvoty::class;
+use super::monitor;
+use super::vol::VolKind;
+use super::{bri, notif, notif_center};
+
+// the stylesheet that ships with dvvidget, used when css_path doesn't point anywhere
+pub const DEFAULT_CSS: &str = include_str!("../../style.css");
+
+// gtk styles every window with every provider, the later layers win on the same selectors
+const WIDGET_PRIORITY: u32 = gtk4::STYLE_PROVIDER_PRIORITY_USER + 1;
+const MONITOR_PRIORITY: u32 = gtk4::STYLE_PROVIDER_PRIORITY_USER + 2;
+
+enum SheetKind {
+    // falls back to the built-in stylesheet, the others to nothing
+    General,
+    // the classes of the widget, every rule has to name one of them
+    Widget(&'static str, Vec<String>),
+    // same for the classes of the monitors it applies to
+    Monitor(Vec<String>),
+}
+
+impl SheetKind {
+    fn priority(&self) -> u32 {
+        match self {
+            SheetKind::General => gtk4::STYLE_PROVIDER_PRIORITY_USER,
+            SheetKind::Widget(..) => WIDGET_PRIORITY,
+            SheetKind::Monitor(_) => MONITOR_PRIORITY,
+        }
+    }
+
+    // the classes every rule has to name, what they belong to and what else they'd restyle
+    fn scope(&self) -> Option<(&[String], String, &str)> {
+        match self {
+            SheetKind::General => None,
+            SheetKind::Widget(widget, classes) => {
+                Some((classes, format!("{} class", widget), "widgets"))
+            }
+            SheetKind::Monitor(classes) => {
+                Some((classes, format!(".{}", classes.join(" or .")), "monitors"))
+            }
+        }
+    }
+}
+
+struct Stylesheet {
+    path: PathBuf,
+    kind: SheetKind,
+    provider: CssProvider,
+}
+
+fn is_class_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+fn has_class(selector: &str, class: &str) -> bool {
+    selector
+        .match_indices(&format!(".{}", class))
+        .any(|(ind, m)| {
+            !selector[ind + m.len()..]
+                .chars()
+                .next()
+                .is_some_and(is_class_char)
+        })
+}
+
+// where the comment or string starting at ind ends, None if there is none there
+fn skip_literal(css: &str, ind: usize) -> Option<usize> {
+    let rest = &css[ind..];
+
+    if let Some(body) = rest.strip_prefix("/*") {
+        return Some(body.find("*/").map_or(css.len(), |end| ind + end + 4));
+    }
+
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let mut escaped = false;
+    for (off, c) in rest.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return Some(ind + off + 1),
+            _ => {}
+        }
+    }
+
+    Some(css.len())
+}
+
+fn blank(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars().map(|c| if c == '\n' { '\n' } else { ' ' })
+}
+
+// comments turn into spaces, so offsets into the result still point at the same place
+fn strip_comments(text: &str) -> String {
+    let mut res = String::new();
+    let mut ind = 0;
+
+    while ind < text.len() {
+        if text[ind..].starts_with("/*") {
+            let end = skip_literal(text, ind).unwrap_or(text.len());
+            res.extend(blank(&text[ind..end]));
+            ind = end;
+            continue;
+        }
+        let c = text[ind..].chars().next().unwrap_or(' ');
+        res.push(c);
+        ind += c.len_utf8();
+    }
+
+    res
+}
+
+// gtk has no way to keep a stylesheet to some windows, so the rules of a widget's stylesheet that
+// name none of its classes would restyle every other widget too
+// those are blanked out, newlines kept so gtk's line numbers still match the file. the rules in @
+// blocks are checked the same way, only keyframes have no selectors to check
+fn scope_rules(css: &str, classes: &[String]) -> (String, Vec<(usize, String)>) {
+    let mut res = String::with_capacity(css.len());
+    let mut skipped = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut ind = 0;
+
+    while ind < css.len() {
+        if let Some(end) = skip_literal(css, ind) {
+            ind = end;
+            continue;
+        }
+
+        let c = css[ind..].chars().next().unwrap_or(' ');
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        ind += c.len_utf8();
+
+        // a statement ends at the } closing its block, or at ; outside of any like @import
+        if depth != 0 || (c != '}' && c != ';') {
+            continue;
+        }
+
+        let statement = &css[start..ind];
+        let stripped = strip_comments(statement);
+        let selector = stripped.split('{').next().unwrap_or("");
+        let lead = selector.len() - selector.trim_start().len();
+        let selector = selector.trim();
+
+        let scoped = c == ';'
+            || selector.starts_with("@keyframes")
+            || (!selector.starts_with('@')
+                && selector
+                    .split(',')
+                    .all(|sel| classes.iter().any(|class| has_class(sel, class))));
+
+        if scoped {
+            res.push_str(statement);
+        } else if let (true, Some(open)) = (selector.starts_with('@'), stripped.find('{')) {
+            let body = &statement[open + 1..statement.len() - 1];
+            let (body, inner) = scope_rules(body, classes);
+            let offset = css[..start + open].matches('\n').count();
+            skipped.extend(
+                inner
+                    .into_iter()
+                    .map(|(line, selector)| (line + offset, selector)),
+            );
+
+            res.push_str(&statement[..=open]);
+            res.push_str(&body);
+            res.push('}');
+        } else {
+            let line = css[..start + lead].matches('\n').count() + 1;
+            skipped.push((
+                line,
+                selector.split_whitespace().collect::<Vec<_>>().join(" "),
+            ));
+
+            res.extend(blank(statement));
+        }
+
+        start = ind;
+    }
+    res.push_str(&css[start..]);
+
+    (res, skipped)
+}
+
+impl Stylesheet {
+    fn new(path: &str, kind: SheetKind, display: &gdk::Display) -> Self {
+        let provider = CssProvider::new();
+
+        // a symlink into a dotfiles repo gets the real file watched
+        let path = PathBuf::from(path);
+        let path = path.canonicalize().unwrap_or(path);
+
+        // gtk skips the broken rules and keeps the rest, this is the only place they show up
+        let name = path.clone();
+        provider.connect_parsing_error(move |_, section, error| {
+            // scoped stylesheets are loaded from a string and have no file to go by
+            let file = section
+                .file()
+                .and_then(|f| f.path())
+                .unwrap_or(name.clone());
+            let file = if file.exists() {
+                file.display().to_string()
+            } else {
+                "built-in stylesheet".to_string()
+            };
+            let location = section.start_location();
+
+            println!(
+                "Css: {}:{}:{}: {}",
+                file,
+                location.lines() + 1,
+                location.line_chars() + 1,
+                error.message()
+            );
+        });
+
+        gtk4::style_context_add_provider_for_display(display, &provider, kind.priority());
+
+        let sheet = Stylesheet {
+            path,
+            kind,
+            provider,
+        };
+        sheet.load();
+        sheet
+    }
+
+    // the provider stays where it is, only what's in it changes
+    fn load(&self) {
+        if let (true, Some((classes, what, others))) = (self.path.exists(), self.kind.scope()) {
+            let css = match std::fs::read_to_string(&self.path) {
+                Ok(css) => css,
+                Err(e) => {
+                    println!("Css: Failed to read {}: {}", self.path.display(), e);
+                    String::new()
+                }
+            };
+
+            let (css, skipped) = scope_rules(&css, classes);
+            for (line, selector) in skipped {
+                println!(
+                    "Css: {}:{}: {} names no {}, skipping it so it doesn't restyle the other {}",
+                    self.path.display(),
+                    line,
+                    selector,
+                    what,
+                    others
+                );
+            }
+
+            self.provider.load_from_string(&css);
+        } else if self.path.exists() {
+            self.provider.load_from_path(&self.path);
+        } else if let SheetKind::General = self.kind {
+            println!(
+                "Css: {} doesn't exist, going with the default stylesheet",
+                self.path.display()
+            );
+            self.provider.load_from_string(DEFAULT_CSS);
+        } else {
+            println!("Css: {} doesn't exist, skipping it", self.path.display());
+            self.provider.load_from_string("");
+        }
+    }
+}
+
+pub struct CssContext {
+    sheets: Vec<Stylesheet>,
+    watcher: Option<RecommendedWatcher>,
+    watched: Vec<PathBuf>,
+}
+
+// the general stylesheet, then the widgets', then the ones of the connected monitors
+fn config_stylesheets(config: &AppConf) -> Vec<(&str, SheetKind)> {
+    let mut res = vec![(config.general.css_path.as_str(), SheetKind::General)];
+
+    let notif_classes = notif::css_classes()
+        .into_iter()
+        .chain(notif_center::css_classes())
+        .collect();
+
+    res.extend(
+        [
+            (&config.vol.css_path, "volume", VolKind::Sink.css_classes()),
+            (
+                &config.mic.css_path,
+                "microphone",
+                VolKind::Source.css_classes(),
+            ),
+            (&config.bri.css_path, "brightness", bri::css_classes()),
+            (&config.dvoty.css_path, "dvoty", class::css_classes()),
+            (&config.denote.css_path, "notification", notif_classes),
+        ]
+        .into_iter()
+        .filter_map(|(path, widget, classes)| {
+            path.as_ref()
+                .map(|path| (path.as_str(), SheetKind::Widget(widget, classes)))
+        }),
+    );
+
+    res.extend(
+        config
+            .general
+            .monitor_css
+            .iter()
+            .map(|(name, path)| (path.as_str(), monitor::css_classes(name)))
+            .filter(|(_, classes)| !classes.is_empty())
+            .map(|(path, classes)| (path, SheetKind::Monitor(classes))),
+    );
+
+    res
+}
+
+impl CssContext {
+    pub fn new(sender: UnboundedSender<notify::Result<Event>>) -> Self {
+        let watcher = notify::recommended_watcher(move |res| {
+            sender.send(res).unwrap_or_else(|e| {
+                println!("Css: Cannot send file event: {}", e);
+            });
+        })
+        .map_err(|e| println!("Css: Failed to watch the stylesheets: {}", e))
+        .ok();
+
+        CssContext {
+            sheets: vec![],
+            watcher,
+            watched: vec![],
+        }
+    }
+
+    // on start, on config reloads and when monitors come and go
+    pub fn apply(&mut self, config: &AppConf) {
+        let display = gdk::Display::default().expect("Cannot open display");
+
+        for sheet in self.sheets.drain(..) {
+            gtk4::style_context_remove_provider_for_display(&display, &sheet.provider);
+        }
+
+        self.sheets = config_stylesheets(config)
+            .into_iter()
+            .map(|(path, kind)| Stylesheet::new(path, kind, &display))
+            .collect();
+
+        self.watch();
+    }
+
+    // whole directories are watched so editors replacing the files are caught too
+    fn watch(&mut self) {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+
+        for sheet in &self.sheets {
+            let Some(dir) = sheet.path.parent() else {
+                continue;
+            };
+
+            if self.watched.iter().any(|d| d == dir) {
+                continue;
+            }
+
+            if watcher
+                .watch(dir, notify::RecursiveMode::NonRecursive)
+                .is_ok()
+            {
+                self.watched.push(dir.to_path_buf());
+            }
+        }
+    }
+
+    pub fn is_css_change(&self, evt: &Event) -> bool {
+        matches!(
+            evt.kind,
+            notify::EventKind::Modify(_)
+                | notify::EventKind::Create(_)
+                | notify::EventKind::Remove(_)
+        ) && evt.paths.iter().any(|p| self.contains(p))
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.sheets.iter().any(|sheet| sheet.path == path)
+    }
+
+    // the windows restyle on their own, nothing is rebuilt
+    pub fn reload(&self, paths: &[PathBuf]) {
+        for sheet in self.sheets.iter().filter(|s| paths.contains(&s.path)) {
+            println!("Css: Reloading {}", sheet.path.display());
+            sheet.load();
+        }
+    }
+}
+
+// every class the widgets set, by widget
+pub fn css_classes() -> Vec<(&'static str, Vec<String>)> {
+    vec![
+        ("volume", VolKind::Sink.css_classes()),
+        ("microphone", VolKind::Source.css_classes()),
+        ("brightness", bri::css_classes()),
+        ("dvoty", class::css_classes()),
+        ("notifications", notif::css_classes()),
+        ("notification center", notif_center::css_classes()),
+    ]
+}
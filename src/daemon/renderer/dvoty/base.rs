@@ -24,7 +24,8 @@ pub enum DvotyTaskType {
 
 #[derive(Default)]
 pub struct DvotyContext {
-    pub dvoty_tasks: Vec<HashMap<DvotyTaskType, JoinHandle<()>>>,
+    // by monitor id
+    pub dvoty_tasks: HashMap<String, HashMap<DvotyTaskType, JoinHandle<()>>>,
     pub dvoty_list: Vec<Option<ListBox>>,
     pub dvoty_scroll: Vec<Option<ScrolledWindow>>,
     pub dvoty_entries: Vec<Vec<(DvotyUIEntry, ListBoxRow)>>,
@@ -36,7 +37,7 @@ pub struct DvotyContext {
 impl DvotyContext {
     pub fn from_config(_config: &Arc<AppConf>, monitor_count: usize) -> Self {
         DvotyContext {
-            dvoty_tasks: create_list_of(monitor_count),
+            dvoty_tasks: HashMap::new(),
             dvoty_list: create_list_of(monitor_count),
             dvoty_scroll: create_list_of(monitor_count),
             dvoty_entries: create_list_of(monitor_count),
@@ -45,6 +46,33 @@ impl DvotyContext {
             should_autofill: vec![true; monitor_count],
         }
     }
+
+    // monitors from position on get a new dvoty window, so they start over
+    // the widgets are looked up by index, the tasks by the id of the monitor
+    pub fn reset_monitors(&mut self, position: usize, monitor_count: usize, rebuilt: &[String]) {
+        for id in rebuilt {
+            if let Some(tasks) = self.dvoty_tasks.remove(id) {
+                tasks.into_values().for_each(|handle| handle.abort());
+            }
+        }
+
+        let start = position.min(self.dvoty_list.len());
+
+        self.dvoty_list.truncate(start);
+        self.dvoty_list.resize_with(monitor_count, Default::default);
+        self.dvoty_scroll.truncate(start);
+        self.dvoty_scroll
+            .resize_with(monitor_count, Default::default);
+        self.dvoty_entries.truncate(start);
+        self.dvoty_entries
+            .resize_with(monitor_count, Default::default);
+        self.cur_ind.truncate(start);
+        self.cur_ind.resize(monitor_count, 0);
+        self.target_scroll.truncate(start);
+        self.target_scroll.resize(monitor_count, 0f64);
+        self.should_autofill.truncate(start);
+        self.should_autofill.resize(monitor_count, true);
+    }
 }
 
 fn input(
@@ -87,6 +87,31 @@ fn create_log_dir(exec: &str) -> Result<(File, File), std::boxed::Box<dyn std::e
 }
 
 impl DvotyUIEntry {
+    pub const CSS_NAMES: [&str; 8] = [
+        "instruction",
+        "math",
+        "launch",
+        "cmd",
+        "search",
+        "url",
+        "letter",
+        "file",
+    ];
+
+    // the kind in the css classes, dvoty-entry-<name>
+    pub fn css_name(&self) -> &'static str {
+        match self {
+            DvotyUIEntry::Instruction => "instruction",
+            DvotyUIEntry::Math { .. } => "math",
+            DvotyUIEntry::Launch { .. } => "launch",
+            DvotyUIEntry::Command { .. } => "cmd",
+            DvotyUIEntry::Search { .. } => "search",
+            DvotyUIEntry::Url { .. } => "url",
+            DvotyUIEntry::Letter { .. } => "letter",
+            DvotyUIEntry::File { .. } => "file",
+        }
+    }
+
     pub fn run(self, config: Arc<AppConf>) {
         match self {
             DvotyUIEntry::Math { result } => {
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use gtk4::{prelude::EditableExt, prelude::WidgetExt, Window};
-use once_cell::sync::OnceCell;
+use once_cell::sync::Lazy;
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
@@ -16,7 +16,35 @@ use std::sync::Mutex;
 
 use super::{utils::get_input, DvotyContext};
 
-pub static CURRENT_IDS: OnceCell<Vec<Arc<Mutex<uuid::Uuid>>>> = OnceCell::new();
+// the latest search per monitor, older searches stop once it changes
+pub static CURRENT_IDS: Lazy<Mutex<Vec<uuid::Uuid>>> = Lazy::new(|| Mutex::new(vec![]));
+
+pub fn is_current(monitor: usize, id: &uuid::Uuid) -> bool {
+    CURRENT_IDS
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .get(monitor)
+        .is_some_and(|cur| cur == id)
+}
+
+pub fn set_current(monitor: usize, id: uuid::Uuid) {
+    if let Some(cur) = CURRENT_IDS
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .get_mut(monitor)
+    {
+        *cur = id;
+    }
+}
+
+// keeps one id per monitor, the ones from position on start over
+pub fn reset_current_ids(position: usize, count: usize) {
+    let mut guard = CURRENT_IDS.lock().unwrap_or_else(|p| p.into_inner());
+    guard.truncate(position);
+    while guard.len() < count {
+        guard.push(uuid::Uuid::new_v4());
+    }
+}
 
 fn handle_dvoty_cmd_single(
     cmd: Dvoty,
@@ -203,29 +231,31 @@ pub fn handle_dvoty_cmd(
     monitors: Vec<usize>,
     id: Option<uuid::Uuid>,
 ) -> Result<DaemonRes, DaemonErr> {
+    let mut result = Ok(DaemonRes::Success);
+
     // dvoty events all only have one monitor, so it's fine to have one id
     for monitor in monitors {
         if let Some(uuid) = id {
-            if *CURRENT_IDS.get().unwrap()[monitor]
-                .lock()
-                .unwrap_or_else(|p| p.into_inner())
-                != uuid
-            {
+            if !is_current(monitor, &uuid) {
                 continue;
             }
         }
 
-        let _ = handle_dvoty_cmd_single(
+        // keep going for the other monitors, the last error is reported
+        if let Err(e) = handle_dvoty_cmd_single(
             cmd.clone(),
             windows,
             sender.clone(),
             context,
             config.clone(),
             monitor,
-        );
+        ) {
+            println!("Dvoty: Failed on monitor {}: {}", monitor, e);
+            result = Err(e);
+        }
     }
 
-    Ok(DaemonRes::Success)
+    result
 }
 
 pub fn send_inc(sender: UnboundedSender<DaemonEvt>, monitor: Vec<usize>) {
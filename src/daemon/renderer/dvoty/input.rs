@@ -5,14 +5,14 @@ use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     daemon::{
-        renderer::config::AppConf,
+        renderer::{config::AppConf, monitor::monitor_id},
         structs::{DaemonCmdType, DaemonEvt, Dvoty},
     },
     utils::{cache_dir, DaemonErr},
 };
 
 use super::{
-    event::CURRENT_IDS, general::process_general, utils::get_input, DvotyContext, DvotyEntry,
+    event::set_current, general::process_general, utils::get_input, DvotyContext, DvotyEntry,
     DvotyTaskType,
 };
 
@@ -136,9 +136,7 @@ pub fn process_input(
     recent_paths: Vec<PathBuf>,
 ) -> Result<(), DaemonErr> {
     let id = uuid::Uuid::new_v4();
-    {
-        *CURRENT_IDS.get().unwrap()[monitor].lock().unwrap() = id;
-    }
+    set_current(monitor, id);
 
     if context.should_autofill[monitor] {
         // find cache
@@ -186,18 +184,17 @@ pub fn process_input(
 
     list.remove_all();
 
-    let task_map = &mut context.dvoty_tasks;
+    let task_map = context.dvoty_tasks.entry(monitor_id(monitor)).or_default();
 
-    if let Some(handle) = task_map[monitor].get(&DvotyTaskType::ProcessInput) {
+    if let Some(handle) = task_map.remove(&DvotyTaskType::ProcessInput) {
         handle.abort();
-        task_map[monitor].remove(&DvotyTaskType::ProcessInput);
     }
 
     let handle = tokio::spawn(async move {
         process_input_str(&input, sender.clone(), config, monitor, recent_paths, id).await;
     });
 
-    task_map[monitor].insert(DvotyTaskType::ProcessInput, handle);
+    task_map.insert(DvotyTaskType::ProcessInput, handle);
 
     Ok(())
 }
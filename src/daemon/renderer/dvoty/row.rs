@@ -8,7 +8,10 @@ use gtk4::{
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
-    daemon::structs::{DaemonCmdType, DaemonEvt, Dvoty},
+    daemon::{
+        renderer::monitor::monitor_id,
+        structs::{DaemonCmdType, DaemonEvt, Dvoty},
+    },
     utils::DaemonErr,
 };
 
@@ -48,21 +51,18 @@ fn init_murph(
     current: f64,
     monitor: usize,
 ) {
-    if context.dvoty_tasks[monitor].contains_key(&DvotyTaskType::MurphViewport) {
-        context.dvoty_tasks[monitor]
-            .get(&DvotyTaskType::MurphViewport)
-            .unwrap()
-            .abort();
-        context.dvoty_tasks[monitor].remove(&DvotyTaskType::MurphViewport);
-    }
-
     let target = context.target_scroll[monitor];
+    let task_map = context.dvoty_tasks.entry(monitor_id(monitor)).or_default();
+
+    if let Some(handle) = task_map.remove(&DvotyTaskType::MurphViewport) {
+        handle.abort();
+    }
 
     let handle = tokio::spawn(async move {
         murph(sender, target, current, monitor).await;
     });
 
-    context.dvoty_tasks[monitor].insert(DvotyTaskType::MurphViewport, handle);
+    task_map.insert(DvotyTaskType::MurphViewport, handle);
 }
 
 fn adjust_row(
@@ -10,7 +10,7 @@ use uuid::Uuid;
 use crate::daemon::renderer::config::AppConf;
 use crate::daemon::renderer::config::SearchEngine;
 use crate::daemon::renderer::dvoty::app_launcher::underline_string;
-use crate::daemon::renderer::dvoty::event::CURRENT_IDS;
+use crate::daemon::renderer::dvoty::event::is_current;
 use crate::daemon::structs::DaemonCmdType;
 use crate::daemon::structs::DaemonEvt;
 use crate::daemon::structs::Dvoty;
@@ -115,11 +115,7 @@ LIMIT {};
                     .await?;
 
                 for place in places {
-                    if *id
-                        != *CURRENT_IDS.get().unwrap()[monitor]
-                            .lock()
-                            .unwrap_or_else(|p| p.into_inner())
-                    {
+                    if !is_current(monitor, id) {
                         return Ok(());
                     }
 
@@ -175,11 +171,7 @@ LIMIT {};",
                     .await?;
 
                 for place in places.iter() {
-                    if *id
-                        != *CURRENT_IDS.get().unwrap()[monitor]
-                            .lock()
-                            .unwrap_or_else(|p| p.into_inner())
-                    {
+                    if !is_current(monitor, id) {
                         break;
                     }
 
@@ -1,7 +1,13 @@
 pub mod app;
 pub mod bri;
 pub mod config;
+pub mod config_check;
+pub mod config_init;
+pub mod config_source;
+pub mod css;
 pub mod dvoty;
+pub mod monitor;
 pub mod notif;
+pub mod notif_center;
 pub mod vol;
 pub mod window;
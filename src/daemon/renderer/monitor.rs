@@ -0,0 +1,194 @@
+use std::sync::RwLock;
+
+use gtk4::gdk;
+use gtk4::prelude::*;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use super::config::ShowOn;
+use crate::daemon::compositor;
+use crate::daemon::structs::MonitorClient;
+use crate::utils::DaemonErr;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorInfo {
+    pub connector: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+}
+
+impl MonitorInfo {
+    pub fn from_monitor(monitor: &gdk::Monitor) -> Self {
+        MonitorInfo {
+            connector: monitor.connector().map(|s| s.to_string()),
+            manufacturer: monitor.manufacturer().map(|s| s.to_string()),
+            model: monitor.model().map(|s| s.to_string()),
+        }
+    }
+
+    // survives the monitor moving around in the list, unlike its index
+    pub fn id(&self) -> String {
+        self.connector
+            .clone()
+            .or_else(|| self.model.clone())
+            .unwrap_or_default()
+    }
+
+    // every window on the monitor carries it, for the per-monitor stylesheets
+    pub fn css_class(&self) -> String {
+        let id: String = self
+            .id()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+
+        format!("monitor-{}", id)
+    }
+}
+
+lazy_static! {
+    // the monitors gtk currently knows about, in the order the cli indexes them
+    pub static ref MONITORS: RwLock<Vec<MonitorInfo>> = RwLock::new(vec![]);
+}
+
+// what per-monitor state is kept under, so it follows the monitor when the list shifts
+pub fn monitor_id(ind: usize) -> String {
+    let guard = match MONITORS.read() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    guard.get(ind).map(MonitorInfo::id).unwrap_or_default()
+}
+
+pub fn list_monitors(display: &gdk::Display) -> Vec<gdk::Monitor> {
+    let monitors = display.monitors();
+
+    (&monitors)
+        .into_iter()
+        .flatten()
+        .filter_map(|monitor| monitor.downcast::<gdk::Monitor>().ok())
+        .collect()
+}
+
+pub fn set_monitors(monitors: &[gdk::Monitor]) {
+    let mut guard = match MONITORS.write() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    *guard = monitors.iter().map(MonitorInfo::from_monitor).collect();
+}
+
+fn by_connector(monitors: &[MonitorInfo], name: &str) -> Option<usize> {
+    monitors
+        .iter()
+        .position(|info| info.connector.as_deref() == Some(name))
+}
+
+// identical monitors share a model, so this can hit more than one
+fn by_name(monitors: &[MonitorInfo], name: &str) -> Vec<usize> {
+    if let Some(ind) = by_connector(monitors, name) {
+        return vec![ind];
+    }
+
+    monitors
+        .iter()
+        .enumerate()
+        .filter(|(_, info)| {
+            info.model
+                .as_deref()
+                .is_some_and(|model| model.eq_ignore_ascii_case(name))
+        })
+        .map(|(ind, _)| ind)
+        .collect()
+}
+
+// the compositor can take up to a second to answer, so it's asked before MONITORS is locked and
+// only when the answer is needed
+fn focused_output(monitor: &MonitorClient, show_on: &ShowOn) -> Option<String> {
+    match (monitor, show_on) {
+        (MonitorClient::Focused, _) | (MonitorClient::Default, ShowOn::Focused) => {
+            compositor::focused_output()
+        }
+        _ => None,
+    }
+}
+
+fn focused(monitors: &[MonitorInfo], output: Option<String>) -> Option<usize> {
+    output.and_then(|name| by_connector(monitors, &name))
+}
+
+// falls back to every monitor, a widget that shows up nowhere is worse than one on every screen
+fn pick(monitors: &[MonitorInfo], show_on: &ShowOn, output: Option<String>) -> Vec<usize> {
+    let mut res = match show_on {
+        ShowOn::All => vec![],
+        ShowOn::Focused => focused(monitors, output).into_iter().collect(),
+        ShowOn::Monitors(names) => names
+            .iter()
+            .flat_map(|name| by_name(monitors, name))
+            .collect(),
+    };
+    res.sort();
+    res.dedup();
+
+    if res.is_empty() {
+        (0..monitors.len()).collect()
+    } else {
+        res
+    }
+}
+
+// names are looked up for every request, the same monitor can sit at another index by now
+pub fn resolve(monitor: &MonitorClient, show_on: &ShowOn) -> Result<Vec<usize>, DaemonErr> {
+    let output = focused_output(monitor, show_on);
+    let guard = match MONITORS.read() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    match monitor {
+        MonitorClient::Default => Ok(pick(&guard, show_on, output)),
+        MonitorClient::All => Ok((0..guard.len()).collect()),
+        MonitorClient::One(ind) => Ok(vec![*ind]),
+        MonitorClient::Name(name) => {
+            let matches = by_name(&guard, name);
+            if matches.is_empty() {
+                Err(DaemonErr::UnknownMonitorName(name.clone()))
+            } else {
+                Ok(matches)
+            }
+        }
+        MonitorClient::Focused => focused(&guard, output)
+            .map(|ind| vec![ind])
+            .ok_or(DaemonErr::NoFocusedMonitor),
+    }
+}
+
+// the classes of the connected monitors going by name, none if it isn't plugged in
+pub fn css_classes(name: &str) -> Vec<String> {
+    let guard = match MONITORS.read() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    by_name(&guard, name)
+        .into_iter()
+        .map(|ind| guard[ind].css_class())
+        .collect()
+}
+
+// for events the daemon raises on its own
+pub fn show_on_monitors(show_on: &ShowOn) -> Vec<usize> {
+    let output = focused_output(&MonitorClient::Default, show_on);
+    match MONITORS.read() {
+        Ok(g) => pick(&g, show_on, output),
+        Err(poisoned) => pick(&poisoned.into_inner(), show_on, output),
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use gtk4::{
+    gdk, glib, pango, prelude::*, Application, ApplicationWindow, Box, Button, GestureClick, Image,
+    Label, Window,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::daemon::notification::denote::{
+    CloseReason, ImageData, Notification, NotificationEvt, NotificationReply, Urgency, DND,
+};
+use crate::daemon::notification::history::NotificationHistory;
+use crate::daemon::structs::{DaemonCmdType, DaemonEvt, DaemonRes, Notif};
+use crate::utils::{DaemonErr, DisplayBackend};
+
+use super::config::AppConf;
+use super::monitor::show_on_monitors;
+use super::notif_center::refresh_center;
+use super::{app::register_widget, window};
+
+pub struct NotifContext {
+    // newest first
+    pub notifications: Vec<Notification>,
+    pub reply_sender: UnboundedSender<NotificationReply>,
+    pub history: NotificationHistory,
+    // notification id -> history id, only valid for this session
+    pub history_ids: HashMap<u32, u64>,
+}
+
+impl NotifContext {
+    pub fn from_config(
+        _config: &Arc<AppConf>,
+        reply_sender: UnboundedSender<NotificationReply>,
+    ) -> Self {
+        NotifContext {
+            notifications: vec![],
+            reply_sender,
+            history: NotificationHistory::load(),
+            history_ids: HashMap::new(),
+        }
+    }
+
+    fn record(&mut self, notification: &Notification, config: &AppConf) {
+        if !config.denote.history || notification.transient {
+            return;
+        }
+
+        let replaced = self
+            .history_ids
+            .get(&notification.id)
+            .is_some_and(|id| self.history.replace(*id, notification));
+
+        if !replaced {
+            let id = self
+                .history
+                .push(notification, config.denote.history_length);
+            self.history_ids.insert(notification.id, id);
+        }
+
+        self.history.save();
+    }
+
+    fn reply(&self, reply: NotificationReply) {
+        self.reply_sender
+            .send(reply)
+            .unwrap_or_else(|e| println!("Notif: Failed to reply to the server: {}", e));
+    }
+}
+
+fn send_notif(sender: &UnboundedSender<DaemonEvt>, cmd: Notif) {
+    sender
+        .send(DaemonEvt {
+            evt: DaemonCmdType::Notif(cmd),
+            sender: None,
+            uuid: None,
+            monitors: vec![],
+        })
+        .unwrap_or_else(|e| println!("Notif: Failed to send event: {}", e));
+}
+
+// xdg activation token so the application can focus its window after the action
+fn activation_token() -> Option<String> {
+    let display = gdk::Display::default()?;
+    display
+        .app_launch_context()
+        .startup_notify_id(None::<&gio::AppInfo>, &[])
+        .map(|token| token.to_string())
+}
+
+fn texture_from_data(data: &ImageData) -> gdk::MemoryTexture {
+    let format = if data.has_alpha {
+        gdk::MemoryFormat::R8g8b8a8
+    } else {
+        gdk::MemoryFormat::R8g8b8
+    };
+
+    gdk::MemoryTexture::new(
+        data.width,
+        data.height,
+        format,
+        &glib::Bytes::from(&data.data),
+        data.rowstride as usize,
+    )
+}
+
+// either a file uri, an absolute path, or an icon name from the theme
+fn image_from_str(icon: &str) -> Image {
+    let path = icon.strip_prefix("file://").unwrap_or(icon);
+    if path.starts_with('/') {
+        Image::from_file(path)
+    } else {
+        Image::from_icon_name(icon)
+    }
+}
+
+// the spec gives image-data the highest priority, then image-path, then app_icon
+fn create_icon(notification: &Notification) -> Option<Image> {
+    let image = if let Some(data) = &notification.image_data {
+        Image::from_paintable(Some(&texture_from_data(data)))
+    } else if let Some(path) = &notification.image_path {
+        image_from_str(path)
+    } else if !notification.app_icon.is_empty() {
+        image_from_str(&notification.app_icon)
+    } else {
+        return None;
+    };
+
+    image.add_css_class("notif-icon");
+    image.set_valign(gtk4::Align::Start);
+
+    Some(image)
+}
+
+fn create_label(text: &str, class: &str, markup: bool) -> Label {
+    let label = Label::builder()
+        .css_classes([class])
+        .halign(gtk4::Align::Start)
+        .xalign(0.0)
+        .wrap(true)
+        .wrap_mode(pango::WrapMode::WordChar)
+        .build();
+
+    // fall back to plain text if the client sent broken markup
+    if markup && pango::parse_markup(text, '\0').is_ok() {
+        label.set_markup(text);
+    } else {
+        label.set_text(text);
+    }
+
+    label
+}
+
+// notif-box also gets notif-box-<urgency>
+const CSS_CLASSES: [&str; 13] = [
+    "notif-window",
+    "notif-wrapper",
+    "notif-box",
+    "notif-main",
+    "notif-header",
+    "notif-icon",
+    "notif-app-name",
+    "notif-time",
+    "notif-content",
+    "notif-summary",
+    "notif-body",
+    "notif-actions",
+    "notif-action",
+];
+
+pub fn css_classes() -> Vec<String> {
+    CSS_CLASSES
+        .iter()
+        .map(|class| class.to_string())
+        .chain(
+            Urgency::ALL
+                .iter()
+                .map(|urgency| format!("notif-box-{}", urgency.as_str())),
+        )
+        .collect()
+}
+
+fn create_notif_box(notification: &Notification, sender: UnboundedSender<DaemonEvt>) -> Box {
+    let app_name = create_label(&notification.app_name, "notif-app-name", false);
+
+    let time = Label::builder()
+        .label(
+            DateTime::<Local>::from(notification.timestamp)
+                .format("%H:%M")
+                .to_string(),
+        )
+        .css_classes(["notif-time"])
+        .halign(gtk4::Align::End)
+        .hexpand(true)
+        .build();
+
+    let header = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .css_classes(["notif-header"])
+        .build();
+    header.append(&app_name);
+    header.append(&time);
+
+    let content = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .css_classes(["notif-content"])
+        .hexpand(true)
+        .build();
+    content.append(&header);
+    content.append(&create_label(&notification.summary, "notif-summary", false));
+
+    if !notification.body.is_empty() {
+        content.append(&create_label(&notification.body, "notif-body", true));
+    }
+
+    let main = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .css_classes(["notif-main"])
+        .build();
+
+    if let Some(icon) = create_icon(notification) {
+        main.append(&icon);
+    }
+    main.append(&content);
+
+    // clicking the notification itself triggers the default action if there is one
+    let id = notification.id;
+    let has_default = notification.actions.iter().any(|(key, _)| key == "default");
+    let sender_clone = sender.clone();
+    let gesture_click = GestureClick::new();
+    gesture_click.connect_pressed(move |_, _, _, _| {
+        if has_default {
+            send_notif(&sender_clone, Notif::InvokeAction(id, "default".into()));
+        } else {
+            send_notif(&sender_clone, Notif::Dismiss(id));
+        }
+    });
+    main.add_controller(gesture_click);
+
+    let result = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .css_classes([
+            "notif-box",
+            &format!("notif-box-{}", notification.urgency.as_str()),
+        ])
+        .build();
+    result.append(&main);
+
+    let buttons = notification
+        .actions
+        .iter()
+        .filter(|(key, _)| key != "default")
+        .map(|(key, label)| {
+            let button = Button::builder()
+                .label(label)
+                .css_classes(["notif-action"])
+                .hexpand(true)
+                .build();
+
+            let key = key.clone();
+            let sender = sender.clone();
+            button.connect_clicked(move |_| {
+                send_notif(&sender, Notif::InvokeAction(id, key.clone()));
+            });
+
+            button
+        })
+        .collect::<Vec<Button>>();
+
+    if !buttons.is_empty() {
+        let actions = Box::builder()
+            .orientation(gtk4::Orientation::Horizontal)
+            .css_classes(["notif-actions"])
+            .homogeneous(true)
+            .build();
+
+        for button in buttons.iter() {
+            actions.append(button);
+        }
+
+        result.append(&actions);
+    }
+
+    result
+}
+
+// popups only show up on the monitors of [denote] show_on, looked up again every time
+fn refresh(
+    windows: &[Window],
+    context: &NotifContext,
+    sender: UnboundedSender<DaemonEvt>,
+    config: &AppConf,
+) {
+    let shown = show_on_monitors(&config.denote.show_on);
+
+    for (monitor, window) in windows.iter().enumerate() {
+        let wrapper = if let Some(w) = window.child().and_downcast::<Box>() {
+            w
+        } else {
+            println!("Notif: can't find the box");
+            continue;
+        };
+
+        while let Some(child) = wrapper.first_child() {
+            wrapper.remove(&child);
+        }
+
+        for notification in context.notifications.iter() {
+            wrapper.append(&create_notif_box(notification, sender.clone()));
+        }
+
+        window.set_visible(!context.notifications.is_empty() && shown.contains(&monitor));
+    }
+}
+
+pub fn handle_notification(
+    evt: NotificationEvt,
+    windows: &[Window],
+    center_windows: &[Window],
+    sender: UnboundedSender<DaemonEvt>,
+    context: &mut NotifContext,
+    config: Arc<AppConf>,
+) {
+    match evt {
+        NotificationEvt::Notify(notification) => {
+            context.record(&notification, &config);
+            refresh_center(center_windows, context, sender.clone());
+
+            // replaced notifications keep their position in the stack
+            if let Some(old) = context
+                .notifications
+                .iter_mut()
+                .find(|n| n.id == notification.id)
+            {
+                *old = *notification;
+            } else {
+                context.notifications.insert(0, *notification);
+            }
+
+            // the ones pushed off the stack are gone for good, their apps are told like on expiry
+            let max = config.denote.max_notifications;
+            if context.notifications.len() > max {
+                for evicted in context.notifications.split_off(max) {
+                    context.reply(NotificationReply::Closed(evicted.id, CloseReason::Expired));
+                }
+            }
+        }
+
+        NotificationEvt::Record(notification) => {
+            context.record(&notification, &config);
+            refresh_center(center_windows, context, sender.clone());
+
+            // a replacement that is not allowed to popup takes the old one down too
+            context.notifications.retain(|n| n.id != notification.id);
+        }
+
+        NotificationEvt::Close(id) => {
+            context.notifications.retain(|n| n.id != id);
+        }
+    }
+
+    refresh(windows, context, sender, &config);
+}
+
+fn set_center_visible(
+    center_windows: &[Window],
+    context: &NotifContext,
+    sender: UnboundedSender<DaemonEvt>,
+    monitors: &[usize],
+    visible: bool,
+) {
+    if visible {
+        refresh_center(center_windows, context, sender);
+    }
+
+    for monitor in monitors {
+        center_windows[*monitor].set_visible(visible);
+    }
+}
+
+pub fn handle_notif_cmd(
+    cmd: Notif,
+    windows: &[Window],
+    center_windows: &[Window],
+    sender: UnboundedSender<DaemonEvt>,
+    context: &mut NotifContext,
+    config: Arc<AppConf>,
+    monitors: Vec<usize>,
+) -> Result<DaemonRes, DaemonErr> {
+    match cmd {
+        Notif::Dismiss(id) => {
+            context.notifications.retain(|n| n.id != id);
+            context.reply(NotificationReply::Closed(id, CloseReason::Dismissed));
+            refresh(windows, context, sender, &config);
+        }
+
+        Notif::InvokeAction(id, key) => {
+            let resident = context
+                .notifications
+                .iter()
+                .find(|n| n.id == id)
+                .is_some_and(|n| n.resident);
+
+            context.reply(NotificationReply::ActionInvoked(
+                id,
+                key,
+                activation_token(),
+            ));
+
+            // resident notifications stay until they are dismissed explicitly
+            if !resident {
+                context.notifications.retain(|n| n.id != id);
+                context.reply(NotificationReply::Closed(id, CloseReason::Dismissed));
+                refresh(windows, context, sender, &config);
+            }
+        }
+
+        Notif::CenterOpen => {
+            set_center_visible(center_windows, context, sender, &monitors, true);
+        }
+
+        Notif::CenterClose => {
+            set_center_visible(center_windows, context, sender, &monitors, false);
+        }
+
+        Notif::CenterToggle => {
+            for monitor in monitors {
+                let visible = !center_windows[monitor].is_visible();
+                set_center_visible(center_windows, context, sender.clone(), &[monitor], visible);
+            }
+        }
+
+        Notif::Clear(id) => {
+            context.history.remove(id);
+            context.history.save();
+            refresh_center(center_windows, context, sender);
+        }
+
+        Notif::ClearApp(app_name) => {
+            context.history.remove_app(&app_name);
+            context.history.save();
+            refresh_center(center_windows, context, sender);
+        }
+
+        Notif::ClearAll => {
+            context.history.clear();
+            context.history.save();
+            refresh_center(center_windows, context, sender);
+        }
+
+        Notif::SetDnd(dnd) => {
+            DND.store(dnd, Ordering::SeqCst);
+            println!("Notif: Do not disturb {}", if dnd { "on" } else { "off" });
+        }
+
+        Notif::ToggleDnd => {
+            let dnd = !DND.fetch_xor(true, Ordering::SeqCst);
+            println!("Notif: Do not disturb {}", if dnd { "on" } else { "off" });
+        }
+
+        Notif::GetDnd => {
+            return Ok(DaemonRes::GetDnd(DND.load(Ordering::SeqCst)));
+        }
+    }
+
+    Ok(DaemonRes::Success)
+}
+
+pub fn create_notif_popup(
+    backend: DisplayBackend,
+    app: &Application,
+    config: Arc<AppConf>,
+    monitor: &gtk4::gdk::Monitor,
+) -> ApplicationWindow {
+    let result = window::create_window(
+        &backend,
+        app,
+        &config.denote.window,
+        gtk4_layer_shell::KeyboardMode::None,
+        monitor,
+    );
+    result.add_css_class("notif-window");
+
+    let wrapper = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(config.denote.spacing as i32)
+        .width_request(config.denote.width)
+        .css_classes(["notif-wrapper"])
+        .build();
+
+    result.set_child(Some(&wrapper));
+    register_widget(super::app::Widget::Notification, result.id());
+
+    result.present();
+
+    // there is nothing to show before the first notification arrives
+    result.set_visible(false);
+
+    result
+}
@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use gtk4::{
+    pango, prelude::*, Application, ApplicationWindow, Box, Button, Label, ScrolledWindow, Window,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::daemon::notification::denote::Urgency;
+use crate::daemon::notification::history::HistoryEntry;
+use crate::daemon::structs::{DaemonCmdType, DaemonEvt, Notif};
+use crate::utils::DisplayBackend;
+
+use super::config::AppConf;
+use super::notif::NotifContext;
+use super::{app::register_widget, window};
+
+fn send_notif(sender: &UnboundedSender<DaemonEvt>, cmd: Notif) {
+    sender
+        .send(DaemonEvt {
+            evt: DaemonCmdType::Notif(cmd),
+            sender: None,
+            uuid: None,
+            monitors: vec![],
+        })
+        .unwrap_or_else(|e| println!("Notif center: Failed to send event: {}", e));
+}
+
+fn create_button(
+    label: &str,
+    class: &str,
+    cmd: Notif,
+    sender: UnboundedSender<DaemonEvt>,
+) -> Button {
+    let button = Button::builder().label(label).css_classes([class]).build();
+    button.connect_clicked(move |_| {
+        send_notif(&sender, cmd.clone());
+    });
+
+    button
+}
+
+fn create_label(text: &str, class: &str) -> Label {
+    Label::builder()
+        .label(text)
+        .css_classes([class])
+        .halign(gtk4::Align::Start)
+        .xalign(0.0)
+        .wrap(true)
+        .wrap_mode(pango::WrapMode::WordChar)
+        .build()
+}
+
+// notif-center-entry also gets notif-center-entry-<urgency>
+const CSS_CLASSES: [&str; 17] = [
+    "notif-center-window",
+    "notif-center-wrapper",
+    "notif-center-header",
+    "notif-center-title",
+    "notif-center-clear-all",
+    "notif-center-scroll",
+    "notif-center-list",
+    "notif-center-empty",
+    "notif-center-group",
+    "notif-center-group-header",
+    "notif-center-app",
+    "notif-center-clear-app",
+    "notif-center-entry",
+    "notif-center-summary",
+    "notif-center-body",
+    "notif-center-time",
+    "notif-center-clear",
+];
+
+pub fn css_classes() -> Vec<String> {
+    CSS_CLASSES
+        .iter()
+        .map(|class| class.to_string())
+        .chain(
+            Urgency::ALL
+                .iter()
+                .map(|urgency| format!("notif-center-entry-{}", urgency.as_str())),
+        )
+        .collect()
+}
+
+fn create_entry(entry: &HistoryEntry, sender: UnboundedSender<DaemonEvt>) -> Box {
+    let time = Label::builder()
+        .label(
+            DateTime::<Local>::from(entry.timestamp)
+                .format("%m/%d %H:%M")
+                .to_string(),
+        )
+        .css_classes(["notif-center-time"])
+        .halign(gtk4::Align::Start)
+        .build();
+
+    let content = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .hexpand(true)
+        .build();
+    content.append(&time);
+    content.append(&create_label(&entry.summary, "notif-center-summary"));
+
+    if !entry.body.is_empty() {
+        content.append(&create_label(&entry.body, "notif-center-body"));
+    }
+
+    let result = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .css_classes([
+            "notif-center-entry",
+            &format!("notif-center-entry-{}", entry.urgency.as_str()),
+        ])
+        .build();
+    result.append(&content);
+    result.append(&create_button(
+        "✕",
+        "notif-center-clear",
+        Notif::Clear(entry.id),
+        sender,
+    ));
+
+    result
+}
+
+fn create_group(
+    app_name: &str,
+    entries: &[&HistoryEntry],
+    sender: UnboundedSender<DaemonEvt>,
+) -> Box {
+    let title = Label::builder()
+        .label(app_name)
+        .css_classes(["notif-center-app"])
+        .halign(gtk4::Align::Start)
+        .hexpand(true)
+        .build();
+
+    let header = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .css_classes(["notif-center-group-header"])
+        .build();
+    header.append(&title);
+    header.append(&create_button(
+        "Clear",
+        "notif-center-clear-app",
+        Notif::ClearApp(app_name.to_string()),
+        sender.clone(),
+    ));
+
+    let result = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .css_classes(["notif-center-group"])
+        .build();
+    result.append(&header);
+
+    for entry in entries {
+        result.append(&create_entry(entry, sender.clone()));
+    }
+
+    result
+}
+
+fn get_list(window: &Window) -> Option<Box> {
+    window
+        .child()
+        .and_downcast::<Box>()?
+        .last_child()
+        .and_downcast::<ScrolledWindow>()?
+        .child()?
+        .first_child()
+        .and_downcast::<Box>()
+}
+
+pub fn refresh_center(
+    windows: &[Window],
+    context: &NotifContext,
+    sender: UnboundedSender<DaemonEvt>,
+) {
+    for window in windows {
+        let list = if let Some(l) = get_list(window) {
+            l
+        } else {
+            println!("Notif center: can't find the list");
+            continue;
+        };
+
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
+        }
+
+        let groups = context.history.grouped();
+
+        if groups.is_empty() {
+            list.append(&create_label("No notifications", "notif-center-empty"));
+        }
+
+        for (app_name, entries) in groups.iter() {
+            list.append(&create_group(app_name, entries, sender.clone()));
+        }
+    }
+}
+
+pub fn create_notif_center(
+    backend: DisplayBackend,
+    app: &Application,
+    config: Arc<AppConf>,
+    sender: UnboundedSender<DaemonEvt>,
+    monitor: &gtk4::gdk::Monitor,
+) -> ApplicationWindow {
+    let result = window::create_window(
+        &backend,
+        app,
+        &config.denote.center_window,
+        gtk4_layer_shell::KeyboardMode::None,
+        monitor,
+    );
+    result.add_css_class("notif-center-window");
+
+    let title = Label::builder()
+        .label("Notifications")
+        .css_classes(["notif-center-title"])
+        .halign(gtk4::Align::Start)
+        .hexpand(true)
+        .build();
+
+    let header = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .css_classes(["notif-center-header"])
+        .build();
+    header.append(&title);
+    header.append(&create_button(
+        "Clear all",
+        "notif-center-clear-all",
+        Notif::ClearAll,
+        sender,
+    ));
+
+    let list = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(config.denote.spacing as i32)
+        .css_classes(["notif-center-list"])
+        .build();
+
+    let scroll = ScrolledWindow::builder()
+        .css_classes(["notif-center-scroll"])
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .min_content_height(config.denote.center_height)
+        .child(&list)
+        .build();
+
+    let wrapper = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .width_request(config.denote.width)
+        .css_classes(["notif-center-wrapper"])
+        .build();
+    wrapper.append(&header);
+    wrapper.append(&scroll);
+
+    result.set_child(Some(&wrapper));
+    register_widget(super::app::Widget::NotificationCenter, result.id());
+
+    result.present();
+
+    if !config.denote.center_window.visible_on_start {
+        result.set_visible(false);
+    }
+
+    result
+}
@@ -2,45 +2,117 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::daemon::structs::{DaemonEvt, DaemonRes, Vol};
+use crate::daemon::audio::pulse;
+use crate::daemon::structs::{DaemonEvt, DaemonRes, Failure, Vol, VolDevice, VolDeviceInfo};
 use crate::utils::{self, DisplayBackend};
 use crate::{daemon::structs::DaemonCmdType, utils::DaemonErr};
 
-use super::app::{VolBriTaskType, VolBriTaskTypeWindow};
-use super::config::{AppConf, VolCmdProvider};
-use super::{app::register_widget, window};
+use super::app::{VolBriTaskType, VolBriTaskTypeWindow, Widget};
+use super::config::{AppConf, AppConfVol, VolCmdProvider};
+use super::{app::register_widget, monitor::monitor_id, window};
 use gtk4::{
     prelude::*, Adjustment, Application, ApplicationWindow, Box, Image, Label, Scale, Window,
 };
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::task::JoinHandle;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VolKind {
+    Sink,
+    // the microphone
+    Source,
+}
+
+// the parts of the osd with a class of their own, after the prefix of the kind
+const CSS_PARTS: [&str; 5] = ["window", "box", "icon", "label", "scale"];
+
+impl VolKind {
+    pub fn conf(self, config: &AppConf) -> &AppConfVol {
+        match self {
+            VolKind::Sink => &config.vol,
+            VolKind::Source => &config.mic,
+        }
+    }
+
+    pub fn device(self, config: &AppConf) -> VolDevice {
+        match (self, self.conf(config).device.clone()) {
+            (VolKind::Sink, Some(name)) => VolDevice::Sink(name),
+            (VolKind::Sink, None) => VolDevice::DefaultSink,
+            (VolKind::Source, Some(name)) => VolDevice::Source(name),
+            (VolKind::Source, None) => VolDevice::DefaultSource,
+        }
+    }
+
+    pub fn cmd(self, vol: Vol) -> DaemonCmdType {
+        match self {
+            VolKind::Sink => DaemonCmdType::Vol(vol),
+            VolKind::Source => DaemonCmdType::Mic(vol),
+        }
+    }
+
+    fn css_prefix(self) -> &'static str {
+        match self {
+            VolKind::Sink => "sound",
+            VolKind::Source => "mic",
+        }
+    }
+
+    fn css_class(self, part: &str) -> String {
+        format!("{}-{}", self.css_prefix(), part)
+    }
+
+    pub fn css_classes(self) -> Vec<String> {
+        CSS_PARTS.iter().map(|part| self.css_class(part)).collect()
+    }
+
+    fn widget(self) -> Widget {
+        match self {
+            VolKind::Sink => Widget::Volume,
+            VolKind::Source => Widget::Microphone,
+        }
+    }
+}
+
+// logs instead of failing, the osd keeps working without a sound server
+pub fn read_volume(config: &AppConf, kind: VolKind) -> (f64, bool) {
+    get_volume(&kind.conf(config).run_cmd, &kind.device(config)).unwrap_or_else(|e| {
+        println!("Vol: Failed to get volume: {}", e);
+        (0f64, false)
+    })
+}
+
 pub struct VolContext {
+    pub kind: VolKind,
     pub cur_vol: f64,
     pub max_vol: f64,
     pub is_muted: bool,
-    pub vol_tasks_window: Vec<HashMap<VolBriTaskTypeWindow, JoinHandle<()>>>,
+    // by monitor id
+    pub vol_tasks_window: HashMap<String, HashMap<VolBriTaskTypeWindow, JoinHandle<()>>>,
     pub vol_tasks: HashMap<VolBriTaskType, JoinHandle<()>>,
 }
 
 impl VolContext {
-    pub fn from_config(config: &Arc<AppConf>, monitor_count: usize) -> Self {
-        let (cur_vol, is_muted) = get_volume(&config.vol.run_cmd);
+    pub fn from_config(config: &Arc<AppConf>, kind: VolKind) -> Self {
+        let (cur_vol, is_muted) = read_volume(config, kind);
         VolContext {
+            kind,
             cur_vol,
-            max_vol: config.vol.max_vol,
+            max_vol: kind.conf(config).max_vol,
             is_muted,
-            vol_tasks_window: {
-                let mut res = vec![];
-                for _ in 0..monitor_count {
-                    res.push(HashMap::new());
-                }
-                res
-            },
+            vol_tasks_window: HashMap::new(),
             vol_tasks: HashMap::new(),
         }
     }
 
+    // the monitors that get their windows rebuilt lose their tasks with them
+    pub fn reset_monitors(&mut self, rebuilt: &[String]) {
+        for id in rebuilt {
+            if let Some(tasks) = self.vol_tasks_window.remove(id) {
+                tasks.into_values().for_each(|handle| handle.abort());
+            }
+        }
+    }
+
     pub fn set_virtual_volume(&mut self, val: f64) -> f64 {
         if val > self.max_vol {
             self.cur_vol = self.max_vol;
@@ -54,7 +126,7 @@ impl VolContext {
     }
 }
 
-fn update_display_info(config: Arc<AppConf>, window: &[Window], val: f64, is_muted: bool) {
+fn update_display_info(conf: &AppConfVol, window: &[Window], val: f64, is_muted: bool) {
     for window in window.iter() {
         let child = if let Some(w) = window.child() {
             w
@@ -65,9 +137,9 @@ fn update_display_info(config: Arc<AppConf>, window: &[Window], val: f64, is_mut
 
         if let Some(widget) = child.first_child() {
             if let Some(label) = widget.downcast_ref::<Label>() {
-                set_icon(config.clone(), IconRefHolder::Text(label), val, is_muted);
+                set_icon(conf, IconRefHolder::Text(label), val, is_muted);
             } else if let Some(pic) = widget.downcast_ref::<Image>() {
-                set_icon(config.clone(), IconRefHolder::Svg(pic), val, is_muted);
+                set_icon(conf, IconRefHolder::Svg(pic), val, is_muted);
             }
         }
 
@@ -87,25 +159,33 @@ fn murph(
     config: Arc<AppConf>,
     window: &[Window],
     monitor: Vec<usize>,
-) {
+) -> Result<(), DaemonErr> {
     let is_mute = context.is_muted;
+    let kind = context.kind;
+    let before = context.cur_vol;
     // shadowing target to adjust it to an appropriate value
     let target = context.set_virtual_volume(target);
+
+    // the osd only shows what the backend took
+    if let Err(e) = set_volume(&kind.conf(&config).run_cmd, &kind.device(&config), target) {
+        context.cur_vol = before;
+        return Err(DaemonErr::Backend(e));
+    }
+
     let task_map = &mut context.vol_tasks;
     if let Some(handle) = task_map.get(&VolBriTaskType::MurphValue) {
         handle.abort();
         task_map.remove(&VolBriTaskType::MurphValue);
     }
 
-    set_volume(&config.vol.run_cmd, target);
-    update_display_info(config.clone(), window, target, is_mute);
+    update_display_info(kind.conf(&config), window, target, is_mute);
 
     let handle = tokio::spawn(async move {
         for _ in 0..50 {
             current += (target - current) * 0.1f64;
             sender
                 .send(DaemonEvt {
-                    evt: DaemonCmdType::Vol(Vol::SetRough(current)),
+                    evt: kind.cmd(Vol::SetRough(current)),
                     sender: None,
                     uuid: None,
                     monitors: monitor.clone(),
@@ -116,7 +196,7 @@ fn murph(
 
         sender
             .send(DaemonEvt {
-                evt: DaemonCmdType::Vol(Vol::SetRough(target)),
+                evt: kind.cmd(Vol::SetRough(target)),
                 sender: None,
                 uuid: None,
                 monitors: monitor,
@@ -125,6 +205,8 @@ fn murph(
     });
 
     task_map.insert(VolBriTaskType::MurphValue, handle);
+
+    Ok(())
 }
 
 fn set_rough(val: f64, windows: &[Window]) {
@@ -163,7 +245,19 @@ fn set_rough(val: f64, windows: &[Window]) {
     }
 }
 
-fn handle_set_mute(context: &mut VolContext, config: Arc<AppConf>, val: bool, windows: &[Window]) {
+fn handle_set_mute(
+    context: &mut VolContext,
+    config: Arc<AppConf>,
+    val: bool,
+    windows: &[Window],
+) -> Result<(), DaemonErr> {
+    let conf = context.kind.conf(&config);
+    let device = context.kind.device(&config);
+
+    set_mute(&conf.run_cmd, &device, val).map_err(DaemonErr::Backend)?;
+    context.is_muted = val;
+    let vol = read_volume(&config, context.kind).0;
+
     for window in windows.iter() {
         let child = if let Some(w) = window.child() {
             w
@@ -172,17 +266,13 @@ fn handle_set_mute(context: &mut VolContext, config: Arc<AppConf>, val: bool, wi
         };
 
         if let Some(label) = child.first_child().and_downcast_ref::<Label>() {
-            context.is_muted = val;
-            set_mute(&config.vol.run_cmd, val);
-            let vol = get_volume(&config.vol.run_cmd).0;
-            set_icon(config.clone(), IconRefHolder::Text(label), vol, val);
+            set_icon(conf, IconRefHolder::Text(label), vol, val);
         } else if let Some(pic) = child.first_child().and_downcast_ref::<Image>() {
-            context.is_muted = val;
-            set_mute(&config.vol.run_cmd, val);
-            let vol = get_volume(&config.vol.run_cmd).0;
-            set_icon(config.clone(), IconRefHolder::Svg(pic), vol, val);
+            set_icon(conf, IconRefHolder::Svg(pic), vol, val);
         }
     }
+
+    Ok(())
 }
 
 pub fn handle_vol_cmd(
@@ -195,11 +285,11 @@ pub fn handle_vol_cmd(
 ) -> Result<DaemonRes, DaemonErr> {
     match cmd {
         Vol::SetMute(val) => {
-            handle_set_mute(context, config, val, windows);
+            handle_set_mute(context, config, val, windows)?;
         }
         Vol::ToggleMute => {
             let mute = !context.is_muted;
-            handle_set_mute(context, config, mute, windows);
+            handle_set_mute(context, config, mute, windows)?;
         }
         Vol::GetMute => {
             return Ok(DaemonRes::GetMute(context.is_muted));
@@ -210,7 +300,7 @@ pub fn handle_vol_cmd(
         Vol::Set(val) => {
             let current = context.cur_vol;
             let target = utils::round_down(val);
-            murph(sender, current, context, target, config, windows, monitors);
+            murph(sender, current, context, target, config, windows, monitors)?;
         }
         Vol::Get => {
             return Ok(DaemonRes::GetVol(context.cur_vol));
@@ -218,12 +308,12 @@ pub fn handle_vol_cmd(
         Vol::Inc(val) => {
             let current = context.cur_vol;
             let target = utils::round_down(current + val);
-            murph(sender, current, context, target, config, windows, monitors);
+            murph(sender, current, context, target, config, windows, monitors)?;
         }
         Vol::Dec(val) => {
             let current = context.cur_vol;
             let target = utils::round_down(current - val);
-            murph(sender, current, context, target, config, windows, monitors);
+            murph(sender, current, context, target, config, windows, monitors)?;
         }
         Vol::Close => {
             for monitor in monitors {
@@ -235,23 +325,53 @@ pub fn handle_vol_cmd(
                 windows[monitor].set_visible(true);
             }
         }
+        Vol::Sync(val, is_muted) => {
+            // our own changes come back through the watcher as well
+            if (val - context.cur_vol).abs() < 0.5 && is_muted == context.is_muted {
+                return Ok(DaemonRes::Success);
+            }
+
+            if let Some(handle) = context.vol_tasks.remove(&VolBriTaskType::MurphValue) {
+                handle.abort();
+            }
+
+            let val = context.set_virtual_volume(val);
+            context.is_muted = is_muted;
+            set_rough(val, windows);
+            update_display_info(context.kind.conf(&config), windows, val, is_muted);
+
+            let watch_popup = context.kind.conf(&config).watch_popup;
+            if watch_popup > 0f64 {
+                return handle_vol_cmd(
+                    Vol::OpenTimed(watch_popup),
+                    windows,
+                    sender,
+                    context,
+                    config,
+                    monitors,
+                );
+            }
+        }
         Vol::OpenTimed(time) => {
             for monitor in monitors.iter() {
                 windows[*monitor].set_visible(true);
-                let map_ref = &mut context.vol_tasks_window;
-                if let Some(handle) = map_ref[*monitor].get(&VolBriTaskTypeWindow::AwaitClose) {
+                let map_ref = context
+                    .vol_tasks_window
+                    .entry(monitor_id(*monitor))
+                    .or_default();
+                if let Some(handle) = map_ref.remove(&VolBriTaskTypeWindow::AwaitClose) {
                     handle.abort();
-                    map_ref[*monitor].remove(&VolBriTaskTypeWindow::AwaitClose);
                 }
 
                 let sender_clone = sender.clone();
                 let monitors_clone = monitors.clone();
+                let kind = context.kind;
 
                 let handle = tokio::spawn(async move {
                     tokio::time::sleep(Duration::from_secs_f64(time)).await;
 
                     if let Err(e) = sender_clone.send(DaemonEvt {
-                        evt: DaemonCmdType::Vol(Vol::Close),
+                        evt: kind.cmd(Vol::Close),
                         sender: None,
                         uuid: None,
                         monitors: monitors_clone.clone(),
@@ -260,82 +380,177 @@ pub fn handle_vol_cmd(
                     }
                 });
 
-                map_ref[*monitor].insert(VolBriTaskTypeWindow::AwaitClose, handle);
+                map_ref.insert(VolBriTaskTypeWindow::AwaitClose, handle);
                 println!("hi");
             }
         }
+        Vol::List => {
+            return Ok(list_devices().map_or_else(
+                |e| DaemonRes::Failure(Failure::Backend(e)),
+                DaemonRes::VolList,
+            ));
+        }
     }
 
     Ok(DaemonRes::Success)
 }
 
+// commands for other devices skip the osd and go straight to the sound server
+pub fn handle_vol_device_cmd(device: VolDevice, cmd: Vol, config: Arc<AppConf>) -> DaemonRes {
+    let provider = match device {
+        VolDevice::DefaultSource | VolDevice::Source(_) => &config.mic.run_cmd,
+        _ => &config.vol.run_cmd,
+    };
+
+    if let VolCmdProvider::Custom(_) = provider {
+        return DaemonRes::Failure(Failure::InvalidRequest(
+            "Custom commands can only control the device of the osd".into(),
+        ));
+    }
+
+    let res = match cmd {
+        Vol::Get => get_volume(provider, &device).map(|(vol, _)| DaemonRes::GetVol(vol)),
+        Vol::GetMute => get_volume(provider, &device).map(|(_, muted)| DaemonRes::GetMute(muted)),
+        Vol::Set(val) | Vol::SetRough(val) => {
+            set_volume(provider, &device, val.max(0f64)).map(|_| DaemonRes::Success)
+        }
+        Vol::Inc(val) => get_volume(provider, &device)
+            .and_then(|(vol, _)| set_volume(provider, &device, utils::round_down(vol + val)))
+            .map(|_| DaemonRes::Success),
+        Vol::Dec(val) => get_volume(provider, &device)
+            .and_then(|(vol, _)| {
+                set_volume(provider, &device, utils::round_down(vol - val).max(0f64))
+            })
+            .map(|_| DaemonRes::Success),
+        Vol::SetMute(val) => set_mute(provider, &device, val).map(|_| DaemonRes::Success),
+        Vol::ToggleMute => get_volume(provider, &device)
+            .and_then(|(_, muted)| set_mute(provider, &device, !muted))
+            .map(|_| DaemonRes::Success),
+        Vol::List => list_devices().map(DaemonRes::VolList),
+        Vol::Open | Vol::Close | Vol::OpenTimed(_) | Vol::Sync(..) => {
+            return DaemonRes::Failure(Failure::InvalidRequest(
+                "Only the default devices have an osd".into(),
+            ));
+        }
+    };
+
+    res.unwrap_or_else(|e| DaemonRes::Failure(Failure::Backend(e)))
+}
+
+// the native connection works with pipewire-pulse too, so this doesn't depend on the provider
+fn list_devices() -> Result<Vec<VolDeviceInfo>, String> {
+    pulse::with_connection(|conn| conn.list()).map_err(|e| e.to_string())
+}
+
+fn wpctl_target(device: &VolDevice) -> &str {
+    match device {
+        VolDevice::DefaultSink => "@DEFAULT_AUDIO_SINK@",
+        VolDevice::DefaultSource => "@DEFAULT_AUDIO_SOURCE@",
+        VolDevice::Sink(id) | VolDevice::Source(id) | VolDevice::Stream(id) => id,
+    }
+}
+
+// wpctl exits with an error on a device it can't find, the reason goes to stderr
+fn run_wpctl(args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new("wpctl")
+        .args(args)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "`wpctl {}` failed with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 // returns the current volume, if it's muted, return true, if it's not, return false
-fn get_volume(cmd: &VolCmdProvider) -> (f64, bool) {
+pub fn get_volume(cmd: &VolCmdProvider, device: &VolDevice) -> Result<(f64, bool), String> {
     match cmd {
         VolCmdProvider::Wpctl => {
-            let output = if let Ok(out) = std::process::Command::new("wpctl")
-                .arg("get-volume")
-                .arg("@DEFAULT_AUDIO_SINK@")
-                .output()
-            {
-                out
-            } else {
-                return (0f64, false);
-            };
-
-            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stdout = run_wpctl(&["get-volume", wpctl_target(device)])?;
             let volume_str = stdout.split_whitespace().nth(1).unwrap_or_default();
             let mute_str = stdout.split_whitespace().nth(2).unwrap_or_default();
-            (
+            Ok((
                 volume_str.parse::<f64>().unwrap_or_default() * 100f64,
                 mute_str == "[MUTED]",
-            )
+            ))
+        }
+
+        VolCmdProvider::Pulse => {
+            pulse::with_connection(|conn| conn.get_volume(device)).map_err(|e| e.to_string())
         }
 
-        VolCmdProvider::NoCmd => (0f64, false),
+        VolCmdProvider::Custom(cmd) => utils::run_shell(&cmd.get)
+            .ok()
+            .and_then(|out| utils::parse_level(&out, &cmd.regex))
+            .ok_or_else(|| format!("Cannot read the output of `{}`", cmd.get)),
+
+        VolCmdProvider::NoCmd => Ok((0f64, false)),
     }
 }
 
-fn set_volume(cmd: &VolCmdProvider, val: f64) {
+pub fn set_volume(cmd: &VolCmdProvider, device: &VolDevice, val: f64) -> Result<(), String> {
     match cmd {
         VolCmdProvider::Wpctl => {
-            if let Err(e) = std::process::Command::new("wpctl")
-                .arg("set-volume")
-                .arg("@DEFAULT_AUDIO_SINK@")
-                .arg(format!("{}%", val))
-                .output()
-            {
-                println!("Vol: Failed to set volume: {}", e);
-            };
+            run_wpctl(&["set-volume", wpctl_target(device), &format!("{}%", val)]).map(|_| ())
         }
 
-        VolCmdProvider::NoCmd => {}
+        VolCmdProvider::Pulse => {
+            pulse::with_connection(|conn| conn.set_volume(device, val)).map_err(|e| e.to_string())
+        }
+
+        VolCmdProvider::Custom(cmd) => {
+            utils::run_shell(&cmd.set.replace("{value}", &val.to_string()))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+
+        VolCmdProvider::NoCmd => Ok(()),
     }
 }
 
-fn set_mute(cmd: &VolCmdProvider, val: bool) {
+pub fn set_mute(cmd: &VolCmdProvider, device: &VolDevice, val: bool) -> Result<(), String> {
     match cmd {
         VolCmdProvider::Wpctl => {
-            if let Err(e) = std::process::Command::new("wpctl")
-                .arg("set-mute")
-                .arg("@DEFAULT_AUDIO_SINK@")
-                .arg(format!("{}", val as i32))
-                .output()
-            {
-                println!("Vol: Failed to set mute: {}", e);
+            run_wpctl(&["set-mute", wpctl_target(device), &(val as i32).to_string()]).map(|_| ())
+        }
+
+        VolCmdProvider::Pulse => {
+            pulse::with_connection(|conn| conn.set_mute(device, val)).map_err(|e| e.to_string())
+        }
+
+        VolCmdProvider::Custom(custom) => {
+            let run = if let Some(mute) = &custom.mute {
+                Some(mute.replace("{value}", &(val as i32).to_string()))
+            } else if get_volume(cmd, device)?.1 != val {
+                // only toggle if it's not in the requested state already
+                custom.toggle_mute.clone()
+            } else {
+                None
+            };
+
+            match run.map(|c| utils::run_shell(&c)) {
+                Some(Err(e)) => Err(e.to_string()),
+                _ => Ok(()),
             }
         }
 
-        VolCmdProvider::NoCmd => {}
+        VolCmdProvider::NoCmd => Ok(()),
     }
 }
 
-fn set_icon(config: Arc<AppConf>, icon: IconRefHolder, cur_vol: f64, is_muted: bool) {
+fn set_icon(conf: &AppConfVol, icon: IconRefHolder, cur_vol: f64, is_muted: bool) {
     if is_muted {
         match icon {
-            IconRefHolder::Text(label) => label.set_text(&config.vol.mute_icon),
+            IconRefHolder::Text(label) => label.set_text(&conf.mute_icon),
             IconRefHolder::Svg(pic) => {
-                if let Err(e) = utils::set_svg(pic, &config.vol.mute_icon) {
+                if let Err(e) = utils::set_svg(pic, &conf.mute_icon) {
                     println!("Vol: Failed to set icon for mute due to SVG error: {}", e);
                 }
             }
@@ -343,7 +558,7 @@ fn set_icon(config: Arc<AppConf>, icon: IconRefHolder, cur_vol: f64, is_muted: b
         return;
     }
 
-    for icon_descriptor in config.vol.icons.iter() {
+    for icon_descriptor in conf.icons.iter() {
         if cur_vol >= icon_descriptor.lower && cur_vol < icon_descriptor.upper {
             match icon {
                 IconRefHolder::Text(label) => label.set_text(&icon_descriptor.icon),
@@ -368,50 +583,43 @@ pub fn create_sound_osd(
     app: &Application,
     config: Arc<AppConf>,
     monitor: &gtk4::gdk::Monitor,
+    kind: VolKind,
 ) -> ApplicationWindow {
+    let conf = kind.conf(&config);
+
     let result = window::create_window(
         &backend,
         app,
-        &config.vol.window,
+        &conf.window,
         gtk4_layer_shell::KeyboardMode::None,
         monitor,
     );
-    result.add_css_class("sound-window");
+    result.add_css_class(&kind.css_class("window"));
 
-    let (cur_vol, is_muted) = get_volume(&config.vol.run_cmd);
+    let (cur_vol, is_muted) = read_volume(&config, kind);
 
-    let adjustment = Adjustment::new(cur_vol, 0.0, config.vol.max_vol, 0.1, 0.0, 0.0);
+    let adjustment = Adjustment::new(cur_vol, 0.0, conf.max_vol, 0.1, 0.0, 0.0);
 
     let wrapper: Box = Box::new(gtk4::Orientation::Horizontal, 10);
     wrapper.set_halign(gtk4::Align::Center);
-    wrapper.add_css_class("sound-box");
+    wrapper.add_css_class(&kind.css_class("box"));
 
     let text_icon = Label::new(Some(""));
-    text_icon.add_css_class("sound-icon");
+    text_icon.add_css_class(&kind.css_class("icon"));
 
     let svg_icon = Image::new();
-    svg_icon.add_css_class("sound-icon");
+    svg_icon.add_css_class(&kind.css_class("icon"));
 
-    if config.vol.use_svg {
-        set_icon(
-            config.clone(),
-            IconRefHolder::Svg(&svg_icon),
-            cur_vol,
-            is_muted,
-        );
+    if conf.use_svg {
+        set_icon(conf, IconRefHolder::Svg(&svg_icon), cur_vol, is_muted);
     } else {
-        set_icon(
-            config.clone(),
-            IconRefHolder::Text(&text_icon),
-            cur_vol,
-            is_muted,
-        );
+        set_icon(conf, IconRefHolder::Text(&text_icon), cur_vol, is_muted);
     }
 
     let label = Label::new(Some(&(cur_vol as i64).to_string()));
-    label.add_css_class("sound-label");
+    label.add_css_class(&kind.css_class("label"));
 
-    if config.vol.use_svg {
+    if conf.use_svg {
         wrapper.append(&svg_icon);
     } else {
         wrapper.append(&text_icon);
@@ -419,17 +627,17 @@ pub fn create_sound_osd(
 
     let scale = Scale::new(gtk4::Orientation::Horizontal, Some(&adjustment));
     scale.set_width_request(100);
-    scale.add_css_class("sound-scale");
+    scale.add_css_class(&kind.css_class("scale"));
     scale.set_sensitive(false);
     wrapper.append(&scale);
     wrapper.append(&label);
 
     result.set_child(Some(wrapper).as_ref());
-    register_widget(super::app::Widget::Volume, result.id());
+    register_widget(kind.widget(), result.id());
 
     result.present();
 
-    if !config.vol.window.visible_on_start {
+    if !conf.window.visible_on_start {
         result.set_visible(false);
     }
 
@@ -2,7 +2,7 @@ use glib::object::Cast;
 use gtk4::prelude::{GtkWindowExt, NativeExt, WidgetExt};
 use gtk4::{Application, ApplicationWindow};
 use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_inline_default::serde_inline_default;
 use smart_default::SmartDefault;
 use toml::map::Map;
@@ -18,9 +18,13 @@ use x11rb::rust_connection::RustConnection;
 use crate::utils::DisplayBackend;
 
 #[serde_inline_default]
-#[derive(Clone, Deserialize, SmartDefault, Debug)]
+#[derive(Clone, Serialize, Deserialize, SmartDefault, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct WindowDescriptor {
-    #[serde(deserialize_with = "deserialize_layer")]
+    #[serde(
+        deserialize_with = "deserialize_layer",
+        serialize_with = "serialize_layer"
+    )]
     #[default(_code = "Layer::Overlay")]
     #[serde_inline_default(Layer::Overlay)]
     pub layer: Layer,
@@ -46,7 +50,7 @@ pub struct WindowDescriptor {
     #[serde_inline_default(false)]
     pub exclusive: bool,
 
-    #[serde(skip_deserializing)]
+    #[serde(skip)]
     pub keyboard_mode: KeyboardModeWrapper,
 
     #[serde_inline_default(true)]
@@ -55,7 +59,7 @@ pub struct WindowDescriptor {
     pub namespace: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct KeyboardModeWrapper {
     pub inner: KeyboardMode,
 }
@@ -73,19 +77,40 @@ where
     D: serde::Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    Ok(string_to_layer(&s))
+    parse_layer(&s).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "unknown layer \"{}\", expected background, bottom, top or overlay",
+            s
+        ))
+    })
 }
 
-pub fn string_to_layer(str: &str) -> Layer {
+fn serialize_layer<S>(layer: &Layer, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(match layer {
+        Layer::Background => "background",
+        Layer::Bottom => "bottom",
+        Layer::Top => "top",
+        _ => "overlay",
+    })
+}
+
+pub fn parse_layer(str: &str) -> Option<Layer> {
     match str {
-        "background" | "Background" => Layer::Background,
-        "bottom" | "Bottom" => Layer::Bottom,
-        "top" | "Top" => Layer::Top,
-        "overlay" | "Overlay" => Layer::Overlay,
-        _ => Layer::Overlay,
+        "background" | "Background" => Some(Layer::Background),
+        "bottom" | "Bottom" => Some(Layer::Bottom),
+        "top" | "Top" => Some(Layer::Top),
+        "overlay" | "Overlay" => Some(Layer::Overlay),
+        _ => None,
     }
 }
 
+pub fn string_to_layer(str: &str) -> Layer {
+    parse_layer(str).unwrap_or(Layer::Overlay)
+}
+
 impl WindowDescriptor {
     pub fn from_toml(
         toml: &Map<String, Value>,
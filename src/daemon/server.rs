@@ -1,32 +1,49 @@
 use crate::daemon::renderer::dvoty::app_launcher;
-use crate::utils::{get_paths, shutdown, DaemonErr, ExitType, EXIT_BROADCAST, EXIT_SENT};
+use crate::utils::{get_paths, shutdown, DaemonErr, ExitType, EVENT_BROADCAST};
 use anyhow::Context;
 use notify::{Event, Watcher};
 use std::fs;
-use std::path::Path;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::unix::ReadHalf;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
 use tokio::net::UnixStream;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-// use super::notification;
-use super::notification::denote::Notification;
-use super::renderer::config::AppConf;
-use super::structs::{DaemonCmdClient, DaemonCmdType, DaemonEvt, DaemonRes};
+use super::notification;
+use super::notification::denote::{NotificationEvt, NotificationReply};
+use super::protocol::{self, Handshake, PROTOCOL_VERSION};
+use super::renderer::config::{self, current_config, set_config, AppConf, ShowOn};
+use super::renderer::monitor;
+use super::structs::{
+    DaemonCmdClient, DaemonCmdType, DaemonEvt, DaemonRes, Failure, MonitorClient,
+};
+use super::watcher;
 use crate::utils::receive_exit;
 
+// the handshake catches mismatched clients, so the path no longer needs the version
 pub fn default_socket_path() -> String {
-    let val = env!("CARGO_PKG_VERSION").replace(".", "-");
-    if cfg!(not(debug_assertions)) {
-        format!("/tmp/dvvidget-{}.sock", val)
+    let name = if cfg!(not(debug_assertions)) {
+        "dvvidget"
     } else {
-        format!("/tmp/dvvidget-{}-debug.sock", val)
+        "dvvidget-debug"
+    };
+
+    // the runtime dir is private to the user, /tmp is shared so keep users apart there
+    match std::env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => format!("{}/{}.sock", dir, name),
+        _ => format!("/tmp/{}-{}.sock", name, unsafe { libc::getuid() }),
     }
 }
 
+const CONFIG_SETTLE_TIME: Duration = Duration::from_millis(200);
+
 async fn is_active_socket(path: &str) -> bool {
     UnixStream::connect(Path::new(path)).await.is_ok()
 }
@@ -35,9 +52,10 @@ pub async fn run_server(
     config_path: &Path,
     socket_path: Option<String>,
     evt_sender: UnboundedSender<DaemonEvt>,
-    monitor_count: usize,
-    _config: Arc<AppConf>,
-    _notification_sender: UnboundedSender<Notification>,
+    config: Arc<AppConf>,
+    notification_sender: UnboundedSender<NotificationEvt>,
+    notification_reply_receiver: UnboundedReceiver<NotificationReply>,
+    config_sender: UnboundedSender<Arc<AppConf>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let socket_path = if let Some(p) = socket_path {
         p
@@ -54,12 +72,24 @@ pub async fn run_server(
         }
     }
 
-    let listener = if let Ok(res) = tokio::net::UnixListener::bind(Path::new(&socket_path)) {
+    // only the owner gets to control the widgets, the socket is created that way so nobody else
+    // can connect before its permissions are set
+    let umask = unsafe { libc::umask(0o177) };
+    let bound = tokio::net::UnixListener::bind(Path::new(&socket_path));
+    unsafe { libc::umask(umask) };
+
+    let listener = if let Ok(res) = bound {
         Rc::new(res)
     } else {
         shutdown("Failed to initialize the server");
     };
 
+    if let Err(e) = fs::set_permissions(&socket_path, fs::Permissions::from_mode(0o600)) {
+        println!("Failed to restrict the socket permissions: {}", e);
+        let _ = fs::remove_file(&socket_path);
+        shutdown("Refusing to serve a socket others can connect to");
+    }
+
     // file watcher for dvoty app launcher
     let (app_launcher_sender, mut app_launcher_receiver) =
         tokio::sync::mpsc::unbounded_channel::<notify::Result<notify::event::Event>>();
@@ -86,22 +116,35 @@ pub async fn run_server(
     })
     .map_err(|e| DaemonErr::FileWatchError(e.to_string()))?;
 
-    let mut path = config_path.to_path_buf();
-    path.pop();
-
-    let _ = watcher.watch(&path, notify::RecursiveMode::NonRecursive);
+    let mut config_files = config::config_files(config_path);
+    let mut config_dirs = vec![];
+    watch_config_dirs(&mut watcher, &config_files, &mut config_dirs);
 
-    let handles = vec![];
+    let mut handles = vec![];
 
     // denote
-    // if config.denote.enable {
-    //     let handle = notification::start_notification_server(notification_sender).await?;
-    //     handles.push(handle);
-    // }
+    if config.denote.enable {
+        match notification::start_notification_server(
+            notification_sender,
+            notification_reply_receiver,
+            config.clone(),
+        )
+        .await
+        {
+            Ok(handle) => handles.push(handle),
+            Err(e) => println!(
+                "Denote: Failed to start the notification server: {}, notifications won't be shown",
+                e
+            ),
+        }
+    }
+
+    let mut watcher_handles = watcher::start_watchers(config.clone(), evt_sender.clone());
 
     loop {
         tokio::select! {
             Ok(t) = receive_exit() => {
+                handles.extend(watcher_handles);
                 process_exit(t, listener, &socket_path, handles);
 
                 return Ok(());
@@ -110,9 +153,10 @@ pub async fn run_server(
             Ok(res) = listener.accept() => {
                 let stream: UnixStream = res.0;
                 let new_sender = evt_sender.clone();
+                let config = current_config();
 
                 tokio::spawn(async move {
-                    if let Err(e) = handle_connection(stream, new_sender, monitor_count).await {
+                    if let Err(e) = handle_connection(stream, new_sender, config).await {
                         println!("Error reading the command: {:?}, ignoring", e)
                     }
                 });
@@ -130,7 +174,41 @@ pub async fn run_server(
             }
 
             Some(Ok(evt)) = config_file_receiver.recv() => {
-                handle_config_file_evt(evt, listener.clone(), &socket_path);
+                if !is_config_change(&evt, &config_files) {
+                    continue;
+                }
+
+                // editors save in several steps, only the last one matters
+                tokio::time::sleep(CONFIG_SETTLE_TIME).await;
+                while config_file_receiver.try_recv().is_ok() {}
+
+                // includes and drop-ins may have come or gone
+                config_files = config::config_files(config_path);
+                watch_config_dirs(&mut watcher, &config_files, &mut config_dirs);
+
+                let new = match config::reload_config(config_path) {
+                    Ok(c) => Arc::new(c),
+                    Err(e) => {
+                        println!("{}\nKeeping the running config", e);
+                        continue;
+                    }
+                };
+                println!("Config: Reloading");
+
+                if new.denote.enable != current_config().denote.enable {
+                    println!("Config: Restart dvvidget to turn the notification server on or off");
+                }
+
+                set_config(new.clone());
+
+                for handle in watcher_handles.drain(..) {
+                    (handle)();
+                }
+                watcher_handles = watcher::start_watchers(new.clone(), evt_sender.clone());
+
+                if let Err(e) = config_sender.send(new) {
+                    println!("Config: Failed to hand the new config to the widgets: {}", e);
+                }
             }
         }
     }
@@ -157,119 +235,351 @@ fn process_exit(
     }
 }
 
-fn handle_config_file_evt(evt: Event, listener: Rc<tokio::net::UnixListener>, socket_path: &str) {
-    match evt.kind {
-        notify::EventKind::Modify(_)
-        | notify::EventKind::Create(_)
-        | notify::EventKind::Remove(_) => {
-            if EXIT_SENT.load(std::sync::atomic::Ordering::SeqCst) {
-                println!("Sent already");
-                return;
-            }
-
-            if let Err(e) = EXIT_BROADCAST.send(ExitType::Restart) {
-                println!("Err sending restart: {}", e);
-            }
+// whole directories are watched so editors replacing the files are caught too
+fn watch_config_dirs(watcher: &mut impl Watcher, files: &[PathBuf], watched: &mut Vec<PathBuf>) {
+    for file in files {
+        // config.d itself, so new drop-ins show up
+        let dir = if file.is_dir() {
+            file.as_path()
+        } else if let Some(parent) = file.parent() {
+            parent
+        } else {
+            continue;
+        };
 
-            EXIT_SENT.store(true, std::sync::atomic::Ordering::SeqCst);
+        if watched.iter().any(|d| d == dir) {
+            continue;
+        }
 
-            println!("shutting down the server..");
-            drop(listener);
-            fs::remove_file(socket_path).unwrap();
+        if watcher
+            .watch(dir, notify::RecursiveMode::NonRecursive)
+            .is_ok()
+        {
+            watched.push(dir.to_path_buf());
         }
-        _ => {}
     }
 }
 
+// one of the files, or a toml file in a watched config.d
+fn is_config_change(evt: &Event, files: &[PathBuf]) -> bool {
+    matches!(
+        evt.kind,
+        notify::EventKind::Modify(_) | notify::EventKind::Create(_) | notify::EventKind::Remove(_)
+    ) && evt.paths.iter().any(|p| {
+        files.iter().any(|f| {
+            p == f || (p.parent() == Some(f) && p.extension().is_some_and(|ext| ext == "toml"))
+        })
+    })
+}
+
 // forwad the event to channel and return it
 async fn handle_connection(
     mut stream: UnixStream,
     evt_sender: UnboundedSender<DaemonEvt>,
-    monitor_count: usize,
+    config: Arc<AppConf>,
 ) -> Result<(), DaemonErr> {
-    let (mut reader, mut writer) = stream.split();
+    let (reader, mut writer) = stream.split();
+    let mut reader = BufReader::new(reader);
+
+    // json clients open with an object, the rust client with the protocol header
+    let first = match reader.fill_buf().await {
+        Ok(buf) => buf.first().copied(),
+        Err(e) => return Err(DaemonErr::ReadingFailed(e.to_string())),
+    };
+
+    match first {
+        None => return Ok(()),
+        Some(b) if b == b'{' || b.is_ascii_whitespace() => {
+            handle_json(&mut reader, &mut writer, evt_sender, &config).await?
+        }
+        Some(_) => handle_binary(&mut reader, &mut writer, evt_sender, &config).await?,
+    }
+
+    if let Err(e) = writer.shutdown().await {
+        return Err(DaemonErr::ShutdownFailed(e.to_string()));
+    };
+
+    Ok(())
+}
+
+fn has_shutdown(cmd: &DaemonCmdType) -> bool {
+    match cmd {
+        DaemonCmdType::ShutDown => true,
+        DaemonCmdType::Batch(cmds) => cmds.iter().any(has_shutdown),
+        _ => false,
+    }
+}
+
+// every command of a batch goes to the monitors of its own widget
+fn resolve_cmd(
+    monitor: &MonitorClient,
+    cmd: DaemonCmdType,
+    config: &AppConf,
+) -> Result<(DaemonCmdType, Vec<usize>), DaemonErr> {
+    if let DaemonCmdType::Batch(cmds) = cmd {
+        let cmds = cmds
+            .into_iter()
+            .map(|cmd| resolve_cmd(monitor, cmd, config))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok((DaemonCmdType::ResolvedBatch(cmds), vec![]));
+    }
+
+    let monitors = monitor::resolve(monitor, &config.show_on(&cmd))?;
+    Ok((cmd, monitors))
+}
+
+async fn dispatch(
+    evt: DaemonCmdClient,
+    evt_sender: &UnboundedSender<DaemonEvt>,
+    config: &AppConf,
+) -> Result<Option<DaemonRes>, DaemonErr> {
     let (res_sender, mut res_receiver): (UnboundedSender<DaemonRes>, UnboundedReceiver<DaemonRes>) =
         mpsc::unbounded_channel();
 
-    let evt: DaemonCmdClient = match read_cmd(&mut reader).await {
+    println!("Event receiverd from client: {:?}", evt);
+
+    // the server goes down without answering, which would take the rest of the batch's answers
+    // with it
+    if let DaemonCmdType::Batch(cmds) = &evt.cmd {
+        if cmds.iter().any(has_shutdown) {
+            return Ok(Some(DaemonRes::Failure(Failure::InvalidRequest(
+                "ShutDown can't be part of a batch".into(),
+            ))));
+        }
+    }
+
+    let (cmd, monitors) = match resolve_cmd(&evt.monitor, evt.cmd.clone(), config) {
         Ok(res) => res,
-        Err(e) => return Err(e),
+        Err(e) => return Ok(Some(DaemonRes::Failure(e.into()))),
     };
 
-    println!("Event receiverd from client: {:?}", evt);
-
     let cmd = DaemonEvt {
-        evt: evt.cmd.clone(),
+        evt: cmd,
         sender: Some(res_sender),
         uuid: None,
-        monitors: evt.monitor.get_val(monitor_count),
+        monitors,
     };
 
     if let DaemonCmdType::ShutDown = evt.cmd {
         shutdown("Shutting down...");
     }
 
-    if let Err(e) = evt_sender.send(cmd.clone()) {
-        return Err(DaemonErr::SendFailed(e.0));
+    if let Err(e) = evt_sender.send(cmd) {
+        return Err(DaemonErr::SendFailed(Box::new(e.0)));
     };
 
-    if let Some(res) = res_receiver.recv().await {
+    Ok(res_receiver.recv().await)
+}
+
+async fn handle_binary<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    reader: &mut R,
+    writer: &mut W,
+    evt_sender: UnboundedSender<DaemonEvt>,
+    config: &AppConf,
+) -> Result<(), DaemonErr> {
+    let version = match protocol::read_header(reader).await {
+        Ok(Some(v)) => v,
+        Ok(None) => return Err(DaemonErr::BadMagic),
+        Err(e) => return Err(DaemonErr::ReadingFailed(e.to_string())),
+    };
+
+    // always answer with our own version, so the client can tell the user what is running
+    let handshake = bincode::serialize(&Handshake::current())
+        .map_err(|e| DaemonErr::WriteErr(e.to_string()))?;
+    if let Err(e) = protocol::write_header(writer).await {
+        return Err(DaemonErr::WriteErr(e.to_string()));
+    }
+    if let Err(e) = protocol::write_frame(writer, &handshake).await {
+        return Err(DaemonErr::WriteErr(e.to_string()));
+    }
+
+    if version != PROTOCOL_VERSION {
+        return Err(DaemonErr::VersionMismatch(version));
+    }
+
+    let evt: DaemonCmdClient = match read_cmd(reader).await {
+        Ok(Some(res)) => res,
+        // the client only wanted the handshake
+        Ok(None) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if let DaemonCmdType::Subscribe = evt.cmd {
+        return stream_events(reader, writer, evt.monitor, &evt_sender, |res| {
+            let buf = bincode::serialize(res).map_err(|e| e.to_string())?;
+            let mut frame = (buf.len() as u32).to_le_bytes().to_vec();
+            frame.extend_from_slice(&buf);
+            Ok(frame)
+        })
+        .await;
+    }
+
+    if let Some(res) = dispatch(evt, &evt_sender, config).await? {
         let evt_buf = match bincode::serialize(&res).context("Failed to serialize command") {
             Ok(res) => res,
-            Err(e) => return Err(DaemonErr::SerializeError(res, e.to_string())),
+            Err(e) => return Err(DaemonErr::SerializeError(Box::new(res), e.to_string())),
         };
 
-        match writer.write(&(evt_buf.len() as u32).to_le_bytes()).await {
+        if let Err(e) = protocol::write_frame(writer, &evt_buf).await {
+            return Err(DaemonErr::WriteErr(e.to_string()));
+        }
+    } else {
+        println!("Cant ");
+    }
+
+    Ok(())
+}
+
+// one DaemonCmdClient object per line, answered with one DaemonRes per line until the client
+// hangs up
+async fn handle_json<R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin>(
+    reader: &mut R,
+    writer: &mut W,
+    evt_sender: UnboundedSender<DaemonEvt>,
+    config: &AppConf,
+) -> Result<(), DaemonErr> {
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) => return Ok(()),
             Ok(_) => {}
-            Err(e) => {
-                return Err(DaemonErr::WriteErr(e.to_string()));
-            }
+            Err(e) => return Err(DaemonErr::ReadingFailed(e.to_string())),
+        }
+
+        if line.trim().is_empty() {
+            continue;
         }
 
-        if let Err(e) = writer.write_all(&evt_buf).await {
+        let res = match serde_json::from_str::<DaemonCmdClient>(&line) {
+            Ok(evt) if matches!(evt.cmd, DaemonCmdType::Subscribe) => {
+                return stream_events(reader, writer, evt.monitor, &evt_sender, |res| {
+                    let mut buf = serde_json::to_string(res).map_err(|e| e.to_string())?;
+                    buf.push('\n');
+                    Ok(buf.into_bytes())
+                })
+                .await;
+            }
+            Ok(evt) => dispatch(evt, &evt_sender, config)
+                .await?
+                .unwrap_or_else(|| {
+                    DaemonRes::Failure(Failure::Internal("No response from the daemon".into()))
+                }),
+            Err(e) => DaemonRes::Failure(Failure::InvalidRequest(e.to_string())),
+        };
+
+        let mut buf = match serde_json::to_string(&res) {
+            Ok(buf) => buf,
+            Err(e) => return Err(DaemonErr::SerializeError(Box::new(res), e.to_string())),
+        };
+        buf.push('\n');
+
+        if let Err(e) = writer.write_all(buf.as_bytes()).await {
             return Err(DaemonErr::WriteErr(e.to_string()));
         }
-    } else {
-        println!("Cant ");
     }
+}
 
-    if let Err(e) = writer.shutdown().await {
-        return Err(DaemonErr::ShutdownFailed(e.to_string()));
+async fn write_res<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    encode: &impl Fn(&DaemonRes) -> Result<Vec<u8>, String>,
+    res: DaemonRes,
+) -> Result<(), DaemonErr> {
+    let data = match encode(&res) {
+        Ok(data) => data,
+        Err(e) => return Err(DaemonErr::SerializeError(Box::new(res), e)),
     };
 
-    Ok(())
+    writer
+        .write_all(&data)
+        .await
+        .map_err(|e| DaemonErr::WriteErr(e.to_string()))
 }
 
-// the sender will send the struct in the following fashion:
-/**
- * +----------------------------+-------------+
- * | u32 size in littlen endian | actual data |
- * +----------------------------+-------------+
- */
-async fn read_cmd(reader: &mut ReadHalf<'_>) -> Result<DaemonCmdClient, DaemonErr> {
-    let mut msg_len_buf = [0u8; 4];
-    if let Err(e) = reader.read_exact(&mut msg_len_buf).await {
-        return Err(DaemonErr::ReadingFailed(e.to_string()));
+// takes over the connection and writes every event until the client hangs up. the monitors are
+// the ones at the time of subscribing
+async fn stream_events<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    reader: &mut R,
+    writer: &mut W,
+    monitor: MonitorClient,
+    evt_sender: &UnboundedSender<DaemonEvt>,
+    encode: impl Fn(&DaemonRes) -> Result<Vec<u8>, String>,
+) -> Result<(), DaemonErr> {
+    let monitors = match monitor::resolve(&monitor, &ShowOn::All) {
+        Ok(m) => m,
+        Err(e) => return write_res(writer, &encode, DaemonRes::Failure(e.into())).await,
     };
-    let msg_len: u32 = u32::from_le_bytes(msg_len_buf);
 
-    let mut msg_buf = Vec::<u8>::with_capacity(msg_len as usize);
+    // subscribe before asking for the current state so none of it gets lost
+    let mut receiver = EVENT_BROADCAST.subscribe();
+    let (res_sender, mut res_receiver) = mpsc::unbounded_channel::<DaemonRes>();
 
-    // ensure we read all the info
-    while msg_buf.len() < msg_len as usize {
-        if let Err(e) = reader
-            .read_buf(&mut msg_buf)
-            .await
-            .context("Failed to read the command from the client")
-        {
-            return Err(DaemonErr::ReadingFailed(e.to_string()));
-        };
+    if let Err(e) = evt_sender.send(DaemonEvt {
+        evt: DaemonCmdType::Subscribe,
+        sender: Some(res_sender),
+        uuid: None,
+        monitors: vec![],
+    }) {
+        return Err(DaemonErr::SendFailed(Box::new(e.0)));
+    }
+
+    // the current state comes first, the channel closes after its last event
+    while let Some(res) = res_receiver.recv().await {
+        if let DaemonRes::Event(evt) = &res {
+            if evt.monitor().is_some_and(|m| !monitors.contains(&m)) {
+                continue;
+            }
+        }
+        write_res(writer, &encode, res).await?;
+    }
+
+    let mut buf = [0u8; 256];
+
+    loop {
+        tokio::select! {
+            res = receiver.recv() => {
+                let evt = match res {
+                    Ok(evt) => evt,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        println!("Server: A subscriber fell behind, skipped {} events", n);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                };
+
+                if evt.monitor().is_some_and(|m| !monitors.contains(&m)) {
+                    continue;
+                }
+
+                write_res(writer, &encode, DaemonRes::Event(evt)).await?;
+            }
+
+            // only hanging up ends it, whatever else comes in, like a trailing newline after the
+            // request, is ignored
+            res = reader.read(&mut buf) => match res {
+                Ok(0) => return Ok(()),
+                Ok(_) => continue,
+                Err(e) => return Err(DaemonErr::ReadingFailed(e.to_string())),
+            },
+        }
     }
+}
+
+// None if the client hung up before sending anything
+async fn read_cmd<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<DaemonCmdClient>, DaemonErr> {
+    let msg_buf = match protocol::read_frame(reader).await {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(DaemonErr::ReadingFailed(e.to_string())),
+    };
 
-    Ok(
+    Ok(Some(
         match bincode::deserialize(&msg_buf).context("Failed to deserialize command") {
             Ok(val) => val,
             Err(e) => return Err(DaemonErr::DeserializeError(e.to_string())),
         },
-    )
+    ))
 }
@@ -1,32 +1,46 @@
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tokio::sync::mpsc::UnboundedSender;
 use uuid::Uuid;
 
+use super::renderer::app::Widget;
 use super::renderer::dvoty::DvotyEntry;
+use super::renderer::monitor::MonitorInfo;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum DaemonCmdType {
     ShutDown,
     Vol(Vol),
+    // same as Vol but for the default source and the microphone osd
+    Mic(Vol),
+    // Vol for any sink, source or stream, without an osd
+    VolDevice(VolDevice, Vol),
     Bri(Bri),
     Dvoty(Dvoty),
+    Notif(Notif),
+    // keeps the connection open and streams DaemonRes::Event
+    Subscribe,
+    // runs every command in one go, answered with DaemonRes::Batch
+    Batch(Vec<DaemonCmdType>),
+    // answered with DaemonRes::Monitors
+    ListMonitors,
+    // a Batch with the monitors of every command resolved by the server, clients can't send it
+    #[serde(skip)]
+    ResolvedBatch(Vec<(DaemonCmdType, Vec<usize>)>),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum MonitorClient {
+    // the show_on of the widget in the config
+    Default,
     All,
     One(usize),
-}
-
-impl MonitorClient {
-    pub fn get_val(&self, monitor_count: usize) -> Vec<usize> {
-        match self {
-            Self::All => (0..monitor_count).collect(),
-            Self::One(num) => vec![*num],
-        }
-    }
+    // a connector name like DP-1 or a model, resolved by the daemon
+    Name(String),
+    // whichever output the compositor says has focus
+    Focused,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -48,6 +62,28 @@ pub enum Vol {
     Close,
     Open,
     OpenTimed(f64),
+    // the volume and mute state changed outside of dvvidget
+    Sync(f64, bool),
+    List,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum VolDevice {
+    DefaultSink,
+    DefaultSource,
+    Sink(String),
+    Source(String),
+    // the index or the application name of a playing stream
+    Stream(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VolDeviceInfo {
+    pub device: VolDevice,
+    pub description: String,
+    pub volume: f64,
+    pub muted: bool,
+    pub is_default: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -60,6 +96,10 @@ pub enum Bri {
     Close,
     Open,
     OpenTimed(f64),
+    // the brightness changed outside of dvvidget
+    Sync(f64),
+    // a ddc display went from the first value to the second one, the same if it failed
+    Applied(f64, f64),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -77,6 +117,21 @@ pub enum Dvoty {
     Toggle,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Notif {
+    Dismiss(u32),
+    InvokeAction(u32, String),
+    CenterOpen,
+    CenterClose,
+    CenterToggle,
+    Clear(u64),
+    ClearApp(String),
+    ClearAll,
+    SetDnd(bool),
+    ToggleDnd,
+    GetDnd,
+}
+
 #[derive(Debug, Clone)]
 pub struct DaemonEvt {
     pub evt: DaemonCmdType,
@@ -90,6 +145,71 @@ pub enum DaemonRes {
     GetVol(f64),
     GetMute(bool),
     GetBri(f64),
+    GetDnd(bool),
+    VolList(Vec<VolDeviceInfo>),
+    // in index order
+    Monitors(Vec<MonitorInfo>),
+    Event(DaemonEvent),
+    // one result per command of a batch, in order
+    Batch(Vec<DaemonRes>),
     Success,
-    Failure(String),
+    Failure(Failure),
+}
+
+// why the daemon couldn't run a command
+#[derive(Debug, Serialize, Deserialize, Clone, Error)]
+pub enum Failure {
+    #[error("There is no monitor {0}")]
+    UnknownMonitor(usize),
+    #[error("There is no monitor called {0}")]
+    UnknownMonitorName(String),
+    #[error("Cannot tell which monitor has focus")]
+    NoFocusedMonitor,
+    #[error("The {0:?} widget is disabled in the config")]
+    WidgetDisabled(Widget),
+    #[error("Backend failure: {0}")]
+    Backend(String),
+    #[error("Cannot find the window of the widget")]
+    WindowMissing,
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+// pushed to subscribers whenever the state behind a widget changes
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum DaemonEvent {
+    Vol {
+        volume: f64,
+        muted: bool,
+    },
+    Mic {
+        volume: f64,
+        muted: bool,
+    },
+    Bri {
+        monitor: usize,
+        brightness: f64,
+    },
+    Dnd {
+        enabled: bool,
+    },
+    Visible {
+        widget: Widget,
+        monitor: usize,
+        visible: bool,
+    },
+}
+
+impl DaemonEvent {
+    // None for the events that aren't tied to a monitor
+    pub fn monitor(&self) -> Option<usize> {
+        match self {
+            DaemonEvent::Bri { monitor, .. } | DaemonEvent::Visible { monitor, .. } => {
+                Some(*monitor)
+            }
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::Watcher;
+use pulseaudio::protocol::{SubscriptionEventFacility, SubscriptionMask};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::audio::pulse::PulseConnection;
+use super::renderer::config::{AppConf, BriCmdProvider, ShowOn, VolCmdProvider};
+use super::renderer::monitor::show_on_monitors;
+use super::renderer::vol::{read_volume, VolKind};
+use super::structs::{Bri, DaemonCmdType, DaemonEvt, Vol};
+
+const BACKLIGHT_PATH: &str = "/sys/class/backlight";
+
+fn send_evt(sender: &UnboundedSender<DaemonEvt>, evt: DaemonCmdType, show_on: &ShowOn) {
+    sender
+        .send(DaemonEvt {
+            evt,
+            sender: None,
+            uuid: None,
+            monitors: show_on_monitors(show_on),
+        })
+        .unwrap_or_else(|e| println!("Watcher: Failed to send event: {}", e));
+}
+
+fn watched(config: &AppConf, kind: VolKind) -> bool {
+    let conf = kind.conf(config);
+    conf.enable && conf.watch && !matches!(conf.run_cmd, VolCmdProvider::NoCmd)
+}
+
+// keeps a subscription to the sound server open, reconnecting if it goes away
+pub fn start_vol_watcher(
+    config: Arc<AppConf>,
+    sender: UnboundedSender<DaemonEvt>,
+) -> Box<dyn FnOnce()> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_clone = stop.clone();
+
+    let res = std::thread::Builder::new()
+        .name("dvvidget volume watcher".into())
+        .spawn(move || {
+            while !stop_clone.load(Ordering::SeqCst) {
+                let mut conn = match PulseConnection::connect().and_then(|mut c| {
+                    c.subscribe(
+                        SubscriptionMask::SINK
+                            | SubscriptionMask::SOURCE
+                            | SubscriptionMask::SERVER,
+                    )
+                    .map(|_| c)
+                }) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        println!(
+                            "Vol watcher: Cannot subscribe to the sound server: {}, retrying",
+                            e
+                        );
+                        std::thread::sleep(Duration::from_secs(5));
+                        continue;
+                    }
+                };
+
+                while !stop_clone.load(Ordering::SeqCst) {
+                    match conn.next_event() {
+                        Ok(Some(evt)) => {
+                            // server events cover default device changes
+                            let kinds: &[VolKind] = match evt.event_facility {
+                                SubscriptionEventFacility::Sink => &[VolKind::Sink],
+                                SubscriptionEventFacility::Source => &[VolKind::Source],
+                                SubscriptionEventFacility::Server => {
+                                    &[VolKind::Sink, VolKind::Source]
+                                }
+                                _ => &[],
+                            };
+
+                            for kind in kinds {
+                                if !watched(&config, *kind) {
+                                    continue;
+                                }
+
+                                let (vol, muted) = read_volume(&config, *kind);
+                                send_evt(
+                                    &sender,
+                                    kind.cmd(Vol::Sync(vol, muted)),
+                                    &kind.conf(&config).show_on,
+                                );
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            println!("Vol watcher: Lost the sound server: {}, reconnecting", e);
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+    if let Err(e) = res {
+        println!("Vol watcher: Failed to start: {}", e);
+    }
+
+    Box::new(move || stop.store(true, Ordering::SeqCst))
+}
+
+fn read_num(path: &Path) -> Option<f64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_bri(device: &Path) -> Option<f64> {
+    let cur = read_num(&device.join("actual_brightness"))?;
+    let max = read_num(&device.join("max_brightness"))?;
+
+    if max == 0f64 {
+        return None;
+    }
+
+    Some(cur * 100f64 / max)
+}
+
+// writes to brightness and the events the driver raises on actual_brightness both land here
+pub fn start_bri_watcher(
+    sender: UnboundedSender<DaemonEvt>,
+    show_on: ShowOn,
+) -> Result<Box<dyn FnOnce()>, notify::Error> {
+    let devices = std::fs::read_dir(BACKLIGHT_PATH)?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<PathBuf>>();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let evt = match res {
+            Ok(e) => e,
+            Err(e) => {
+                println!("Bri watcher: {}", e);
+                return;
+            }
+        };
+
+        if !matches!(evt.kind, notify::EventKind::Modify(_)) {
+            return;
+        }
+
+        if let Some(val) = evt
+            .paths
+            .first()
+            .and_then(|p| p.parent())
+            .and_then(read_bri)
+        {
+            send_evt(&sender, DaemonCmdType::Bri(Bri::Sync(val)), &show_on);
+        }
+    })?;
+
+    for device in devices.iter() {
+        watcher.watch(device, notify::RecursiveMode::NonRecursive)?;
+    }
+
+    Ok(Box::new(move || drop(watcher)))
+}
+
+pub fn start_watchers(
+    config: Arc<AppConf>,
+    sender: UnboundedSender<DaemonEvt>,
+) -> Vec<Box<dyn FnOnce()>> {
+    let mut handles = vec![];
+
+    if watched(&config, VolKind::Sink) || watched(&config, VolKind::Source) {
+        handles.push(start_vol_watcher(config.clone(), sender.clone()));
+    }
+
+    // displays don't report ddc/ci changes, only backlights can be watched
+    if config.bri.enable && config.bri.watch && !matches!(config.bri.run_cmd, BriCmdProvider::Ddc) {
+        match start_bri_watcher(sender, config.bri.show_on.clone()) {
+            Ok(handle) => handles.push(handle),
+            Err(e) => println!("Bri watcher: Failed to start: {}", e),
+        }
+    }
+
+    handles
+}
@@ -6,6 +6,11 @@ use clap::Parser;
 use cli::args::{self, Args};
 
 fn main() {
-    let args = Args::parse();
-    args::handle_args(args);
+    let batch = args::split_batch(std::env::args().collect());
+
+    if batch.len() > 1 {
+        args::handle_batch(batch.into_iter().map(Args::parse_from).collect());
+    } else {
+        args::handle_args(Args::parse());
+    }
 }
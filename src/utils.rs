@@ -1,9 +1,11 @@
 use std::{path::PathBuf, sync::atomic::AtomicBool, time::Duration};
 use thiserror::Error;
 
-use crate::daemon::structs::{DaemonCmdClient, DaemonEvt, DaemonRes};
+use crate::daemon::renderer::app::Widget;
+use crate::daemon::structs::{DaemonCmdClient, DaemonEvent, DaemonEvt, DaemonRes, Failure};
 use gtk4::Image;
 use once_cell::sync::Lazy;
+use regex::Regex;
 use tokio::sync::broadcast;
 
 pub fn cache_dir() -> PathBuf {
@@ -73,6 +75,15 @@ pub static EXIT_BROADCAST: Lazy<broadcast::Sender<ExitType>> =
 
 pub static EXIT_SENT: AtomicBool = AtomicBool::new(false);
 
+// every subscribed connection holds a receiver
+pub static EVENT_BROADCAST: Lazy<broadcast::Sender<DaemonEvent>> =
+    Lazy::new(|| broadcast::channel(64).0);
+
+pub fn publish_event(evt: DaemonEvent) {
+    // fails when nobody is subscribed, which is fine
+    let _ = EVENT_BROADCAST.send(evt);
+}
+
 pub fn send_exit() -> Result<(), String> {
     if EXIT_SENT.load(std::sync::atomic::Ordering::SeqCst) {
         return Err("Sent already".into());
@@ -117,26 +128,113 @@ pub enum DaemonErr {
     #[error("Failed to deserialize {0}")]
     DeserializeError(String),
     #[error("Failed to send {:?}", .0)]
-    SendFailed(DaemonEvt),
+    SendFailed(Box<DaemonEvt>),
     #[error("Failed to shutdown: {0}")]
     ShutdownFailed(String),
     #[error("Failed to write to stream: {0}")]
     WriteErr(String),
     #[error("Failed to write to serialize evet {:?}: {}", .0, .1)]
-    SerializeError(DaemonRes, String),
+    SerializeError(Box<DaemonRes>, String),
     #[error("Failed to initialize the file watcher: {0}")]
     FileWatchError(String),
     #[error("Failed to find widget")]
     CannotFindWidget,
+    #[error("There is no monitor {0}")]
+    UnknownMonitor(usize),
+    #[error("There is no monitor called {0}")]
+    UnknownMonitorName(String),
+    #[error("Cannot tell which monitor has focus")]
+    NoFocusedMonitor,
+    #[error("The {0:?} widget is disabled")]
+    WidgetDisabled(Widget),
+    #[error("Backend failure: {0}")]
+    Backend(String),
+    #[error("The client doesn't speak the dvvidget protocol")]
+    BadMagic,
+    #[error("The client uses protocol version {0}, expected {expected}", expected = crate::daemon::protocol::PROTOCOL_VERSION)]
+    VersionMismatch(u16),
+}
+
+// exit codes of the client, 2 is what clap uses for usage errors
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NO_DAEMON: i32 = 3;
+pub const EXIT_PROTOCOL: i32 = 4;
+pub const EXIT_IO: i32 = 5;
+// the daemon refused the command, 1 is left for everything it can't tell apart
+pub const EXIT_NO_MONITOR: i32 = 6;
+pub const EXIT_DISABLED: i32 = 7;
+pub const EXIT_BACKEND: i32 = 8;
+
+impl From<DaemonErr> for Failure {
+    fn from(e: DaemonErr) -> Self {
+        match e {
+            DaemonErr::UnknownMonitor(m) => Failure::UnknownMonitor(m),
+            DaemonErr::UnknownMonitorName(name) => Failure::UnknownMonitorName(name),
+            DaemonErr::NoFocusedMonitor => Failure::NoFocusedMonitor,
+            DaemonErr::WidgetDisabled(w) => Failure::WidgetDisabled(w),
+            DaemonErr::CannotFindWidget => Failure::WindowMissing,
+            DaemonErr::Backend(e) => Failure::Backend(e),
+            e => Failure::Internal(e.to_string()),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum ClientErr {
+    #[error("Cannot connect to the daemon, is it running?")]
     CannotConnectServer,
-    SerializeError(DaemonCmdClient, String),
+    #[error("Failed to serialize {:?}: {}", .0, .1)]
+    SerializeError(Box<DaemonCmdClient>, String),
+    #[error("Failed to deserialize the response: {0}")]
     DeserializeError(String),
+    #[error("Failed to read the response: {0}")]
     ReadingFailed(String),
+    #[error("Failed to write to the daemon: {0}")]
     WriteErr(String),
+    #[error("The socket doesn't belong to a dvvidget daemon")]
+    NotADaemon,
+    #[error(
+        "The daemon didn't answer the handshake, it may be older than this client, restart it"
+    )]
+    NoHandshake,
+    #[error(
+        "The daemon (dvvidget {daemon_version}) speaks protocol version {daemon}, this client speaks {client}, restart the daemon"
+    )]
+    VersionMismatch {
+        client: u16,
+        daemon: u16,
+        daemon_version: String,
+    },
+}
+
+impl ClientErr {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ClientErr::CannotConnectServer => EXIT_NO_DAEMON,
+            ClientErr::NotADaemon | ClientErr::NoHandshake | ClientErr::VersionMismatch { .. } => {
+                EXIT_PROTOCOL
+            }
+            ClientErr::SerializeError(..)
+            | ClientErr::DeserializeError(_)
+            | ClientErr::ReadingFailed(_)
+            | ClientErr::WriteErr(_) => EXIT_IO,
+        }
+    }
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Failure::UnknownMonitor(_)
+            | Failure::UnknownMonitorName(_)
+            | Failure::NoFocusedMonitor => EXIT_NO_MONITOR,
+            Failure::WidgetDisabled(_) => EXIT_DISABLED,
+            Failure::Backend(_) => EXIT_BACKEND,
+            Failure::InvalidRequest(_) => EXIT_USAGE,
+            Failure::WindowMissing | Failure::Internal(_) => EXIT_FAILED,
+        }
+    }
 }
 
 pub fn round_down(val: f64) -> f64 {
@@ -147,3 +245,41 @@ pub fn set_svg(pic: &Image, path: &str) -> Result<(), Box<dyn std::error::Error>
     pic.set_from_file(Some(path));
     Ok(())
 }
+
+// runs the command through sh and returns its stdout, a failing command is an error
+pub fn run_shell(cmd: &str) -> std::io::Result<String> {
+    let output = std::process::Command::new("/bin/sh")
+        .arg("-c")
+        .arg(cmd)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "`{}` failed with {}: {}",
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// reads the output of a custom provider, returns the percentage and if it's muted
+pub fn parse_level(output: &str, regex: &Regex) -> Option<(f64, bool)> {
+    let captures = regex.captures(output)?;
+    let value = captures.name("value")?.as_str().parse::<f64>().ok()?;
+    let muted = captures
+        .name("muted")
+        .is_some_and(|m| !m.as_str().is_empty());
+
+    let value = match captures
+        .name("max")
+        .and_then(|m| m.as_str().parse::<f64>().ok())
+    {
+        Some(max) if max > 0f64 => value * 100f64 / max,
+        _ => value,
+    };
+
+    Some((value, muted))
+}